@@ -0,0 +1,40 @@
+use crate::parser::{ParsedCommand, VmCommand};
+
+mod labels;
+pub use labels::check_labels;
+
+mod call_graph;
+pub(crate) use call_graph::is_allowed;
+pub use call_graph::{check_calls, CallGraph, CallSite, FunctionNode, JACK_OS_CLASSES};
+
+/// Commands of a vm file that belong to the same function.
+pub struct FunctionScope<'a> {
+    /// `None` for the commands that come before the first `function` command.
+    pub function_name: Option<&'a str>,
+    /// Starts with the `function` command itself (if any).
+    pub commands: &'a [ParsedCommand],
+}
+
+/// Splits the commands of a file at every `function` command.
+pub fn function_scopes(commands: &[ParsedCommand]) -> Vec<FunctionScope<'_>> {
+    let mut scopes: Vec<FunctionScope> = vec![];
+    let mut start = 0;
+    for i in 1..=commands.len() {
+        let is_scope_end =
+            i == commands.len() || matches!(commands[i].command, VmCommand::Function { .. });
+        if !is_scope_end {
+            continue;
+        }
+        let scope_commands = &commands[start..i];
+        let function_name = match &scope_commands[0].command {
+            VmCommand::Function { name, .. } => Some(name.as_str()),
+            _ => None,
+        };
+        scopes.push(FunctionScope {
+            function_name,
+            commands: scope_commands,
+        });
+        start = i;
+    }
+    return scopes;
+}
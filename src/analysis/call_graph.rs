@@ -0,0 +1,229 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::parser::{Span, VmCommand, VmFile};
+use crate::VmSource;
+
+use super::function_scopes;
+
+/// Classes of the Jack OS. They usually ship as separate (compiled) files, so calls to
+/// their functions are allowed when no translated file defines the class.
+pub const JACK_OS_CLASSES: [&str; 8] = [
+    "Math", "Memory", "Screen", "Output", "String", "Array", "Keyboard", "Sys",
+];
+
+/// A `call` command.
+#[derive(Debug, Clone)]
+pub struct CallSite {
+    pub callee: String,
+    pub n_args: u16,
+    pub file_index: usize,
+    pub span: Span,
+}
+
+/// A `function` command and the calls made from its body.
+#[derive(Debug, Clone)]
+pub struct FunctionNode {
+    pub file_index: usize,
+    pub span: Span,
+    pub calls: Vec<CallSite>,
+}
+
+/// Functions defined across all files of a program and the calls between them.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    /// If a function is defined more than once, the first definition is kept here.
+    pub functions: HashMap<String, FunctionNode>,
+    /// The later definitions of the functions defined more than once, in program order.
+    pub duplicate_functions: Vec<(String, FunctionNode)>,
+    /// Calls made before the first `function` command of a file.
+    pub top_level_calls: Vec<CallSite>,
+}
+
+impl CallGraph {
+    pub fn build(files: &[VmFile]) -> CallGraph {
+        let mut call_graph = CallGraph::default();
+        for (file_index, file) in files.iter().enumerate() {
+            for scope in function_scopes(&file.commands) {
+                let calls: Vec<CallSite> = scope
+                    .commands
+                    .iter()
+                    .filter_map(|parsed| match &parsed.command {
+                        VmCommand::Call { name, n_args } => Some(CallSite {
+                            callee: name.clone(),
+                            n_args: *n_args,
+                            file_index,
+                            span: parsed.span,
+                        }),
+                        _ => None,
+                    })
+                    .collect();
+
+                let name = match scope.function_name {
+                    Some(name) => name,
+                    None => {
+                        call_graph.top_level_calls.extend(calls);
+                        continue;
+                    }
+                };
+                let node = FunctionNode {
+                    file_index,
+                    span: scope.commands[0].span,
+                    calls,
+                };
+                if call_graph.functions.contains_key(name) {
+                    call_graph
+                        .duplicate_functions
+                        .push((name.to_string(), node));
+                } else {
+                    call_graph.functions.insert(name.to_string(), node);
+                }
+            }
+        }
+        return call_graph;
+    }
+
+    /// Returns every function that can be called, directly or not, from `roots`.
+    pub fn reachable_from(&self, roots: &[&str]) -> HashSet<String> {
+        let mut reachable: HashSet<String> = HashSet::new();
+        let mut stack: Vec<&str> = roots.to_vec();
+        while let Some(name) = stack.pop() {
+            if !reachable.insert(name.to_string()) {
+                continue;
+            }
+            if let Some(node) = self.functions.get(name) {
+                stack.extend(node.calls.iter().map(|call| call.callee.as_str()));
+            }
+        }
+        return reachable;
+    }
+}
+
+/// Returns true if `function_name` matches an entry of `allowed` (`Class.function` or `Class.*`).
+pub(crate) fn is_allowed(function_name: &str, allowed: &[String]) -> bool {
+    return allowed.iter().any(|entry| match entry.strip_suffix('*') {
+        Some(prefix) => function_name.starts_with(prefix),
+        None => entry == function_name,
+    });
+}
+
+/// Checks the calls of the whole program (`sources[i]` was parsed into `files[i]`):
+/// - called functions are defined in one of the files (error),
+/// - a function is defined only once (error),
+/// - a function `Foo.bar` is defined in `Foo.vm` (warning),
+/// - all calls to a function pass the same number of arguments (warning).
+///
+/// Calls to the functions in `allowed` and to Jack OS classes that are not part of the
+/// program are not reported.
+pub fn check_calls(sources: &[&VmSource], files: &[VmFile], allowed: &[String]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    let file_name = |file_index: usize| format!("{}.vm", files[file_index].name);
+    let diagnostic = |severity: Severity, file_index: usize, span: Span, message: &str| {
+        Diagnostic::new(
+            severity,
+            &file_name(file_index),
+            &sources[file_index].text,
+            span,
+            message,
+        )
+    };
+
+    let call_graph = CallGraph::build(files);
+
+    let mut defined_classes: HashSet<&str> = HashSet::new();
+    for (file_index, file) in files.iter().enumerate() {
+        for parsed in &file.commands {
+            let name = match &parsed.command {
+                VmCommand::Function { name, .. } => name,
+                _ => continue,
+            };
+            let class_name = name.split('.').next().unwrap_or_default();
+            defined_classes.insert(class_name);
+
+            let first = &call_graph.functions[name];
+            if first.file_index != file_index || first.span != parsed.span {
+                diagnostics.push(diagnostic(
+                    Severity::Error,
+                    file_index,
+                    parsed.span,
+                    &format!(
+                        "function {} is already defined in {} (line {}).",
+                        name,
+                        file_name(first.file_index),
+                        first.span.line
+                    ),
+                ));
+            }
+            if !name.contains('.') || class_name != file.name {
+                diagnostics.push(diagnostic(
+                    Severity::Warning,
+                    file_index,
+                    parsed.span,
+                    &format!(
+                        "function {} should be named {}.<name> since it is defined in {}.",
+                        name,
+                        file.name,
+                        file_name(file_index)
+                    ),
+                ));
+            }
+        }
+    }
+
+    let all_calls = call_graph
+        .functions
+        .values()
+        .chain(call_graph.duplicate_functions.iter().map(|(_, node)| node))
+        .flat_map(|node| &node.calls)
+        .chain(&call_graph.top_level_calls);
+    let mut calls_by_callee: HashMap<&str, Vec<&CallSite>> = HashMap::new();
+    for call in all_calls {
+        calls_by_callee.entry(&call.callee).or_default().push(call);
+    }
+
+    // sorted so that diagnostics are reported in a stable order.
+    let mut callees: Vec<&str> = calls_by_callee.keys().copied().collect();
+    callees.sort();
+    for callee in callees {
+        let mut calls = calls_by_callee[callee].clone();
+        calls.sort_by_key(|call| (call.file_index, call.span.line));
+
+        let class_name = callee.split('.').next().unwrap_or_default();
+        let is_os_function =
+            JACK_OS_CLASSES.contains(&class_name) && !defined_classes.contains(class_name);
+        if !call_graph.functions.contains_key(callee)
+            && !is_os_function
+            && !is_allowed(callee, allowed)
+        {
+            for call in &calls {
+                diagnostics.push(diagnostic(
+                    Severity::Error,
+                    call.file_index,
+                    call.span,
+                    &format!("function {} is not defined in any file.", callee),
+                ));
+            }
+            continue;
+        }
+
+        let first = calls[0];
+        for call in &calls[1..] {
+            if call.n_args != first.n_args {
+                diagnostics.push(diagnostic(
+                    Severity::Warning,
+                    call.file_index,
+                    call.span,
+                    &format!(
+                        "function {} is called with {} argument(s) here but with {} in {} (line {}).",
+                        callee,
+                        call.n_args,
+                        first.n_args,
+                        file_name(first.file_index),
+                        first.span.line
+                    ),
+                ));
+            }
+        }
+    }
+    return diagnostics;
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::parser::{ParsedCommand, Span, VmCommand, VmFile};
+use crate::VmSource;
+
+use super::function_scopes;
+
+/// Commands sharing the same labels: a function, the code of a file outside any
+/// function, or the whole program with `--global-labels`.
+struct LabelScope<'a> {
+    /// e.g. `function Main.main`, used in the messages.
+    name: String,
+    /// Commands of the scope and the file they come from, in program order.
+    parts: Vec<(&'a VmSource, &'a [ParsedCommand])>,
+}
+
+/// Checks the labels of every function (or of the whole program if
+/// `is_global_labels_option` is set, as the labels are then emitted verbatim):
+/// - every `goto`/`if-goto` target is declared in the same scope (error),
+/// - a label is declared only once per scope (error),
+/// - labels are declared inside a function (warning).
+///
+/// `sources[i]` is the source of `files[i]`.
+pub fn check_labels(
+    sources: &[&VmSource],
+    files: &[VmFile],
+    is_global_labels_option: bool,
+) -> Vec<Diagnostic> {
+    let mut scopes: Vec<LabelScope> = vec![];
+    if is_global_labels_option {
+        scopes.push(LabelScope {
+            name: "the program".to_string(),
+            parts: sources
+                .iter()
+                .zip(files)
+                .map(|(source, file)| (*source, file.commands.as_slice()))
+                .collect(),
+        });
+    } else {
+        for (source, file) in sources.iter().zip(files) {
+            for scope in function_scopes(&file.commands) {
+                let name = match scope.function_name {
+                    Some(name) => format!("function {}", name),
+                    None => "the code outside any function".to_string(),
+                };
+                scopes.push(LabelScope {
+                    name,
+                    parts: vec![(*source, scope.commands)],
+                });
+            }
+        }
+    }
+
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    for scope in &scopes {
+        diagnostics.append(&mut check_scope(scope, is_global_labels_option));
+    }
+    return diagnostics;
+}
+
+fn check_scope(scope: &LabelScope, is_global_labels_option: bool) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+
+    // label -> (file name, position) of its first declaration.
+    let mut labels: HashMap<&str, (String, Span)> = HashMap::new();
+    for (source, commands) in &scope.parts {
+        let file_name = format!("{}.vm", source.name);
+        let mut function_name: Option<&str> = None;
+        for parsed in commands.iter() {
+            let label = match &parsed.command {
+                VmCommand::Function { name, .. } => {
+                    function_name = Some(name);
+                    continue;
+                }
+                VmCommand::Label(label) => label,
+                _ => continue,
+            };
+            if function_name.is_none() {
+                diagnostics.push(Diagnostic::warning(
+                    &file_name,
+                    &source.text,
+                    parsed.span,
+                    &format!("label {} is declared outside any function.", label),
+                ));
+            }
+            match labels.get(label.as_str()) {
+                Some((first_file, first)) => {
+                    let location = if is_global_labels_option {
+                        format!("{}, line {}", first_file, first.line)
+                    } else {
+                        format!("line {}", first.line)
+                    };
+                    diagnostics.push(Diagnostic::error(
+                        &file_name,
+                        &source.text,
+                        parsed.span,
+                        &format!(
+                            "label {} is already declared in {} ({}).",
+                            label, scope.name, location
+                        ),
+                    ));
+                }
+                None => {
+                    labels.insert(label, (file_name.clone(), parsed.span));
+                }
+            }
+        }
+    }
+
+    for (source, commands) in &scope.parts {
+        let file_name = format!("{}.vm", source.name);
+        for parsed in commands.iter() {
+            match &parsed.command {
+                VmCommand::Goto(label) | VmCommand::IfGoto(label)
+                    if !labels.contains_key(label.as_str()) =>
+                {
+                    diagnostics.push(Diagnostic::error(
+                        &file_name,
+                        &source.text,
+                        parsed.span,
+                        &format!("label {} is not declared in {}.", label, scope.name),
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+    return diagnostics;
+}
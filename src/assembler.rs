@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// First RAM address given to variables (`@name` without a matching label).
+const VARIABLE_BASE_ADDRESS: u16 = 16;
+
+/// Largest value of an A-instruction (15 bits).
+const MAX_A_VALUE: u16 = 32767;
+
+const PREDEFINED_SYMBOLS: [(&str, u16); 7] = [
+    ("SP", 0),
+    ("LCL", 1),
+    ("ARG", 2),
+    ("THIS", 3),
+    ("THAT", 4),
+    ("SCREEN", 16384),
+    ("KBD", 24576),
+];
+
+/// comp mnemonics (with `A`, the `a` bit is 0) and their `c1..c6` bits.
+const COMP_TABLE: [(&str, u16); 18] = [
+    ("0", 0b101010),
+    ("1", 0b111111),
+    ("-1", 0b111010),
+    ("D", 0b001100),
+    ("A", 0b110000),
+    ("!D", 0b001101),
+    ("!A", 0b110001),
+    ("-D", 0b001111),
+    ("-A", 0b110011),
+    ("D+1", 0b011111),
+    ("A+1", 0b110111),
+    ("D-1", 0b001110),
+    ("A-1", 0b110010),
+    ("D+A", 0b000010),
+    ("D-A", 0b010011),
+    ("A-D", 0b000111),
+    ("D&A", 0b000000),
+    ("D|A", 0b010101),
+];
+
+const JUMP_TABLE: [&str; 8] = ["", "JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"];
+
+/// A HACK assembly instruction. `dest`, `comp` and `jump` are stored in canonical
+/// form (no spaces, `dest` registers in `AMD` order, commutative operands as in
+/// the HACK specification), so two equivalent instructions compare equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// `@value`
+    AConst(u16),
+    /// `@symbol`
+    ASymbol(String),
+    /// `dest=comp;jump`, `dest` and `jump` may be empty.
+    C {
+        dest: String,
+        comp: String,
+        jump: String,
+    },
+    /// `(label)`, is not translated to machine code.
+    Label(String),
+}
+
+impl Instruction {
+    pub fn c(dest: &str, comp: &str, jump: &str) -> Instruction {
+        return Instruction::C {
+            dest: dest.to_string(),
+            comp: comp.to_string(),
+            jump: jump.to_string(),
+        };
+    }
+
+    /// Returns false for labels, which don't take a word in ROM.
+    pub fn is_instruction(&self) -> bool {
+        return !matches!(self, Instruction::Label(_));
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::AConst(value) => write!(f, "@{}", value),
+            Instruction::ASymbol(symbol) => write!(f, "@{}", symbol),
+            Instruction::Label(label) => write!(f, "({})", label),
+            Instruction::C { dest, comp, jump } => {
+                if !dest.is_empty() {
+                    write!(f, "{}=", dest)?;
+                }
+                write!(f, "{}", comp)?;
+                if !jump.is_empty() {
+                    write!(f, ";{}", jump)?;
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// 1-based source line of the offending instruction. A buffer entry holding several
+    /// lines counts as that many lines, as when written to a file one entry per line.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "[ERROR] assembly line {}: {}", self.line, self.message);
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Rewrites a comp mnemonic in the canonical form used by `COMP_TABLE` (with `M`
+/// in place of `A` if needed), e.g. `M & D` becomes `D&M`.
+fn canonical_comp(comp: &str) -> String {
+    let comp: String = comp.chars().filter(|c| !c.is_whitespace()).collect();
+    for op in ['+', '&', '|'] {
+        if let Some((left, right)) = comp.split_once(op) {
+            // canonical order: D first, then A/M; constants last.
+            let is_swapped = (left != "D" && right == "D") || left == "1";
+            if is_swapped {
+                return format!("{}{}{}", right, op, left);
+            }
+        }
+    }
+    return comp;
+}
+
+/// Returns the `a c1..c6` bits of a canonical comp mnemonic.
+fn encode_comp(comp: &str) -> Option<u16> {
+    let (a_bit, comp_with_a) = if comp.contains('M') {
+        (1, comp.replace('M', "A"))
+    } else {
+        (0, comp.to_string())
+    };
+    return COMP_TABLE
+        .iter()
+        .find(|(mnemonic, _)| *mnemonic == comp_with_a)
+        .map(|(_, bits)| (a_bit << 6) | bits);
+}
+
+/// Symbols: letters, digits, `_`, `.`, `$` and `:`, not beginning with a digit.
+fn is_valid_symbol(symbol: &str) -> bool {
+    return !symbol.is_empty()
+        && !symbol.starts_with(|c: char| c.is_ascii_digit())
+        && symbol
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.$:".contains(c));
+}
+
+/// Parses a line of assembly. Returns `None` for empty lines and comments.
+pub fn parse_instruction(line: &str) -> Result<Option<Instruction>, String> {
+    let line = match line.find("//") {
+        Some(i) => &line[..i],
+        None => line,
+    }
+    .trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some(value) = line.strip_prefix('@') {
+        let value = value.trim();
+        if value.starts_with(|c: char| c.is_ascii_digit()) {
+            return match value.parse::<u16>() {
+                Ok(n) if n <= MAX_A_VALUE => Ok(Some(Instruction::AConst(n))),
+                _ => Err(format!(
+                    "{} is not a constant between 0 and {}.",
+                    value, MAX_A_VALUE
+                )),
+            };
+        }
+        if !is_valid_symbol(value) {
+            return Err(format!("{} is not a valid symbol.", value));
+        }
+        return Ok(Some(Instruction::ASymbol(value.to_string())));
+    }
+
+    if let Some(label) = line.strip_prefix('(') {
+        return match label.strip_suffix(')') {
+            Some(label) if is_valid_symbol(label.trim()) => {
+                Ok(Some(Instruction::Label(label.trim().to_string())))
+            }
+            _ => Err(format!("{} is not a valid label declaration.", line)),
+        };
+    }
+
+    // dest=comp;jump
+    let (dest, rest) = match line.split_once('=') {
+        Some((dest, rest)) => (dest.trim(), rest),
+        None => ("", line),
+    };
+    let (comp, jump) = match rest.split_once(';') {
+        Some((comp, jump)) => (comp, jump.trim()),
+        None => (rest, ""),
+    };
+
+    if !dest.chars().all(|c| "AMD".contains(c)) || (line.contains('=') && dest.is_empty()) {
+        return Err(format!("{} is not a valid destination.", dest));
+    }
+    // canonical dest order, e.g. `MD` and `DM` are the same destination.
+    let dest: String = "AMD".chars().filter(|c| dest.contains(*c)).collect();
+
+    let comp = canonical_comp(comp);
+    if encode_comp(&comp).is_none() {
+        return Err(format!("{} is not a valid computation.", comp));
+    }
+    if !JUMP_TABLE.contains(&jump) || (rest.contains(';') && jump.is_empty()) {
+        return Err(format!("{} is not a valid jump.", jump));
+    }
+
+    return Ok(Some(Instruction::C {
+        dest,
+        comp,
+        jump: jump.to_string(),
+    }));
+}
+
+/// Parses lines of assembly, as produced by `CodeGenerator` (comments allowed).
+pub fn parse_asm<S: AsRef<str>>(lines: &[S]) -> Result<Vec<Instruction>, AsmError> {
+    return Ok(parse_numbered_asm(lines)?
+        .into_iter()
+        .map(|(_, instruction)| instruction)
+        .collect());
+}
+
+/// Parses lines of assembly, returning each instruction with its 1-based source line.
+fn parse_numbered_asm<S: AsRef<str>>(lines: &[S]) -> Result<Vec<(usize, Instruction)>, AsmError> {
+    let mut instructions: Vec<(usize, Instruction)> = vec![];
+    let mut line_number = 0;
+    for line in lines {
+        // a single buffer entry may hold several lines (e.g. debug comments).
+        for sub_line in line.as_ref().split('\n') {
+            line_number += 1;
+            match parse_instruction(sub_line) {
+                Ok(Some(instruction)) => instructions.push((line_number, instruction)),
+                Ok(None) => {}
+                Err(message) => {
+                    return Err(AsmError {
+                        line: line_number,
+                        message,
+                    })
+                }
+            }
+        }
+    }
+    return Ok(instructions);
+}
+
+/// Number of ROM words taken by lines of assembly (labels and comments take none).
+pub fn rom_size<S: AsRef<str>>(lines: &[S]) -> Result<usize, AsmError> {
+    return Ok(parse_asm(lines)?
+        .iter()
+        .filter(|instruction| instruction.is_instruction())
+        .count());
+}
+
+/// Returns the ROM address of every label.
+pub fn label_addresses(instructions: &[Instruction]) -> HashMap<String, usize> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut rom_address: usize = 0;
+    for instruction in instructions {
+        match instruction {
+            Instruction::Label(label) => {
+                labels.entry(label.clone()).or_insert(rom_address);
+            }
+            _ => rom_address += 1,
+        }
+    }
+    return labels;
+}
+
+/// Translates instructions to 16-bit machine code.
+///
+/// Labels are resolved to ROM addresses, other symbols are predefined (`SP`, `R0`..`R15`,
+/// `SCREEN`, `KBD`, ...) or variables allocated from RAM[16] in order of appearance.
+///
+/// Errors are located as if the instructions were written one per line.
+pub fn assemble(instructions: &[Instruction]) -> Result<Vec<u16>, AsmError> {
+    let labels = label_addresses(instructions);
+    let mut symbols: HashMap<String, u16> = HashMap::new();
+    for (symbol, address) in PREDEFINED_SYMBOLS {
+        symbols.insert(symbol.to_string(), address);
+    }
+    for register in 0..16 {
+        symbols.insert(format!("R{}", register), register);
+    }
+    let mut next_variable = VARIABLE_BASE_ADDRESS;
+
+    let mut words: Vec<u16> = vec![];
+    for (index, instruction) in instructions.iter().enumerate() {
+        let error = |message: String| AsmError {
+            line: index + 1,
+            message,
+        };
+        match instruction {
+            Instruction::Label(_) => {}
+            Instruction::AConst(value) => words.push(*value),
+            Instruction::ASymbol(symbol) => {
+                let address = if let Some(rom_address) = labels.get(symbol) {
+                    if *rom_address > usize::from(MAX_A_VALUE) {
+                        return Err(error(format!(
+                            "label {} is at ROM address {}, out of the reach of an A-instruction.",
+                            symbol, rom_address
+                        )));
+                    }
+                    *rom_address as u16
+                } else if let Some(address) = symbols.get(symbol) {
+                    *address
+                } else {
+                    let address = next_variable;
+                    symbols.insert(symbol.clone(), address);
+                    next_variable += 1;
+                    address
+                };
+                words.push(address);
+            }
+            Instruction::C { dest, comp, jump } => {
+                let comp_bits = match encode_comp(comp) {
+                    Some(bits) => bits,
+                    None => return Err(error(format!("{} is not a valid computation.", comp))),
+                };
+                let dest_bits = (u16::from(dest.contains('A')) << 2)
+                    | (u16::from(dest.contains('D')) << 1)
+                    | u16::from(dest.contains('M'));
+                let jump_bits = match JUMP_TABLE.iter().position(|j| j == jump) {
+                    Some(bits) => bits as u16,
+                    None => return Err(error(format!("{} is not a valid jump.", jump))),
+                };
+                words.push(0b111 << 13 | comp_bits << 6 | dest_bits << 3 | jump_bits);
+            }
+        }
+    }
+    return Ok(words);
+}
+
+/// Parses and assembles lines of assembly.
+pub fn assemble_lines<S: AsRef<str>>(lines: &[S]) -> Result<Vec<u16>, AsmError> {
+    let numbered = parse_numbered_asm(lines)?;
+    let instructions: Vec<Instruction> = numbered
+        .iter()
+        .map(|(_, instruction)| instruction.clone())
+        .collect();
+    return assemble(&instructions).map_err(|e| AsmError {
+        line: numbered[e.line - 1].0,
+        message: e.message,
+    });
+}
+
+/// Formats machine code in the `.hack` text format: one 16-character binary word per line.
+pub fn to_hack_text(words: &[u16]) -> String {
+    let mut text = String::with_capacity(words.len() * 17);
+    for word in words {
+        text.push_str(&format!("{:016b}\n", word));
+    }
+    return text;
+}
+
+/// Parses the `.hack` text format.
+pub fn parse_hack_text(text: &str) -> Result<Vec<u16>, AsmError> {
+    let mut words: Vec<u16> = vec![];
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match u16::from_str_radix(line, 2) {
+            Ok(word) if line.len() == 16 => words.push(word),
+            _ => {
+                return Err(AsmError {
+                    line: line_index + 1,
+                    message: format!("{} is not a 16-bit binary word.", line),
+                })
+            }
+        }
+    }
+    return Ok(words);
+}
@@ -0,0 +1,321 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::analysis::function_scopes;
+use crate::assembler::{parse_asm, rom_size, AsmError};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::hack_cpu::ROM_SIZE;
+use crate::optimizer::optimize;
+use crate::parser::{ParsedCommand, Segment, VmCommand, VmFile};
+
+/// First RAM address of the static variables.
+pub const STATIC_BASE_ADDRESS: usize = 16;
+/// Number of static variables that fit in RAM 16..255, below the stack.
+pub const STATIC_CAPACITY: usize = 240;
+
+/// Number of contributors listed when a limit is exceeded.
+const LISTED_CONTRIBUTORS: usize = 5;
+
+/// Instructions emitted for the commands of one function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSize {
+    /// Name of the vm file including its extension.
+    pub file: String,
+    /// `None` for the commands before the first `function` of the file.
+    pub function: Option<String>,
+    pub instructions: usize,
+}
+
+impl FunctionSize {
+    pub fn name(&self) -> String {
+        return match &self.function {
+            Some(function) => function.clone(),
+            None => format!("{} (outside functions)", self.file),
+        };
+    }
+}
+
+/// The commands of one function and the code generated for them.
+pub struct FunctionCode<'a> {
+    /// Name of the vm file including its extension.
+    pub file: String,
+    /// `None` for the commands before the first `function` of the file.
+    pub function: Option<&'a str>,
+    pub commands: &'a [ParsedCommand],
+    /// `code_blocks[i]` is the translation of `commands[i]`.
+    pub code_blocks: &'a [Vec<String>],
+}
+
+impl FunctionCode<'_> {
+    /// Size of the function, counted by `count` from its code blocks.
+    pub fn measure<F>(&self, count: F) -> Result<FunctionSize, Diagnostic>
+    where
+        F: FnOnce(&[Vec<String>]) -> Result<usize, AsmError>,
+    {
+        let mut size = FunctionSize {
+            file: self.file.clone(),
+            function: self.function.map(|name| name.to_string()),
+            instructions: 0,
+        };
+        size.instructions =
+            count(self.code_blocks).map_err(|e| invalid_assembly(&size.file, &size.name(), e))?;
+        return Ok(size);
+    }
+}
+
+/// Attributes `code_blocks`, as returned by `CodeGenerator::generate_code_blocks` for
+/// `files`, to the functions of `files` in program order. The first block (bootstrap and
+/// shared routines) belongs to no function.
+pub fn function_code<'a>(
+    files: &'a [VmFile],
+    code_blocks: &'a [Vec<String>],
+) -> Vec<FunctionCode<'a>> {
+    let mut functions: Vec<FunctionCode> = vec![];
+    let mut block_index = 1;
+    for file in files {
+        for scope in function_scopes(&file.commands) {
+            let end = block_index + scope.commands.len();
+            functions.push(FunctionCode {
+                file: format!("{}.vm", file.name),
+                function: scope.function_name,
+                commands: scope.commands,
+                code_blocks: &code_blocks[block_index..end],
+            });
+            block_index = end;
+        }
+    }
+    return functions;
+}
+
+/// Instructions of the bootstrap and shared routines (the first of `code_blocks`),
+/// counted by `count`.
+pub fn measure_startup<F>(
+    files: &[VmFile],
+    code_blocks: &[Vec<String>],
+    count: F,
+) -> Result<usize, Diagnostic>
+where
+    F: FnOnce(&[Vec<String>]) -> Result<usize, AsmError>,
+{
+    return count(&code_blocks[..1]).map_err(|e| {
+        invalid_assembly(
+            &first_file_name(files),
+            "the bootstrap and shared routines",
+            e,
+        )
+    });
+}
+
+/// True if `lines`, the translation of `files`, doesn't fit in ROM or if the statics of
+/// `files` overflow RAM 16..255. Cheaper than building a `SizeReport`.
+pub fn exceeds_limits(files: &[VmFile], lines: &[String]) -> Result<bool, Diagnostic> {
+    let instructions = program_size(files, lines)?;
+    let statics: usize = files.iter().map(static_count).sum();
+    return Ok(instructions > ROM_SIZE || statics > STATIC_CAPACITY);
+}
+
+/// Number of ROM words taken by `lines`, the translation of `files`.
+pub(crate) fn program_size(files: &[VmFile], lines: &[String]) -> Result<usize, Diagnostic> {
+    return rom_size(lines)
+        .map_err(|e| invalid_assembly(&first_file_name(files), "the program", e));
+}
+
+/// ROM and RAM used by a translated program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeReport {
+    /// Instructions of the shared routines and the bootstrap.
+    pub startup_instructions: usize,
+    /// Every function in program order.
+    pub functions: Vec<FunctionSize>,
+    /// Instructions of the whole program (labels excluded).
+    pub total_instructions: usize,
+    /// Distinct `File.i` static variables of every file, in program order.
+    pub statics: Vec<(String, usize)>,
+}
+
+impl SizeReport {
+    /// Counts the instructions of `code_blocks` (as returned by
+    /// `CodeGenerator::generate_code_blocks` for `files`) and the static variables of
+    /// `files`. With `is_optimized`, each function is counted after the peephole optimizer.
+    ///
+    /// `total_instructions` is the size of `lines`, the final output. Returns an error if
+    /// some of the assembly doesn't parse.
+    pub fn new(
+        files: &[VmFile],
+        code_blocks: &[Vec<String>],
+        lines: &[String],
+        is_optimized: bool,
+    ) -> Result<SizeReport, Diagnostic> {
+        let size = |blocks: &[Vec<String>]| -> Result<usize, AsmError> {
+            let lines = blocks.concat();
+            if !is_optimized {
+                return rom_size(&lines);
+            }
+            return Ok(optimize(&parse_asm(&lines)?)
+                .iter()
+                .filter(|instruction| instruction.is_instruction())
+                .count());
+        };
+        return Ok(SizeReport {
+            startup_instructions: measure_startup(files, code_blocks, size)?,
+            functions: function_code(files, code_blocks)
+                .iter()
+                .map(|code| code.measure(size))
+                .collect::<Result<_, _>>()?,
+            total_instructions: rom_size(lines)
+                .map_err(|e| invalid_assembly(&first_file_name(files), "the program", e))?,
+            statics: files
+                .iter()
+                .map(|file| (format!("{}.vm", file.name), static_count(file)))
+                .collect(),
+        });
+    }
+
+    /// Instructions of every file, in program order.
+    pub fn file_instructions(&self) -> Vec<(String, usize)> {
+        let mut files: Vec<(String, usize)> = vec![];
+        for function in &self.functions {
+            match files.last_mut() {
+                Some((file, instructions)) if *file == function.file => {
+                    *instructions += function.instructions
+                }
+                _ => files.push((function.file.clone(), function.instructions)),
+            }
+        }
+        return files;
+    }
+
+    pub fn static_count(&self) -> usize {
+        return self.statics.iter().map(|(_, count)| count).sum();
+    }
+
+    /// Errors for a program that doesn't fit in ROM or whose statics overflow RAM 16..255,
+    /// listing the biggest contributors.
+    pub fn errors(&self) -> Vec<Diagnostic> {
+        let mut errors: Vec<Diagnostic> = vec![];
+        if self.total_instructions > ROM_SIZE {
+            let mut functions: Vec<&FunctionSize> = self.functions.iter().collect();
+            functions.sort_by_key(|function| std::cmp::Reverse(function.instructions));
+            let biggest: Vec<String> = functions
+                .iter()
+                .take(LISTED_CONTRIBUTORS)
+                .map(|function| format!("{} ({})", function.name(), function.instructions))
+                .collect();
+            errors.push(Diagnostic::without_span(
+                Severity::Error,
+                functions.first().map_or("", |function| function.file.as_str()),
+                &format!(
+                    "the program has {} instructions, the HACK ROM holds {}. Biggest functions: {}.",
+                    self.total_instructions,
+                    ROM_SIZE,
+                    biggest.join(", ")
+                ),
+            ));
+        }
+        if self.static_count() > STATIC_CAPACITY {
+            let mut statics: Vec<&(String, usize)> = self.statics.iter().collect();
+            statics.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+            let biggest: Vec<String> = statics
+                .iter()
+                .take(LISTED_CONTRIBUTORS)
+                .map(|(file, count)| format!("{} ({})", file, count))
+                .collect();
+            errors.push(Diagnostic::without_span(
+                Severity::Error,
+                &statics[0].0,
+                &format!(
+                    "the program has {} static variables, RAM {}..{} holds {}. Files with the most statics: {}.",
+                    self.static_count(),
+                    STATIC_BASE_ADDRESS,
+                    STATIC_BASE_ADDRESS + STATIC_CAPACITY - 1,
+                    STATIC_CAPACITY,
+                    biggest.join(", ")
+                ),
+            ));
+        }
+        return errors;
+    }
+}
+
+impl fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |used: usize, capacity: usize| used as f64 * 100.0 / capacity as f64;
+        writeln!(
+            f,
+            "ROM: {} of {} instructions ({:.1}%)",
+            self.total_instructions,
+            ROM_SIZE,
+            percent(self.total_instructions, ROM_SIZE)
+        )?;
+        writeln!(
+            f,
+            "    {:>7}  bootstrap and shared routines",
+            self.startup_instructions
+        )?;
+        for (file, instructions) in self.file_instructions() {
+            writeln!(f, "    {:>7}  {}", instructions, file)?;
+            for function in self
+                .functions
+                .iter()
+                .filter(|function| function.file == file)
+            {
+                writeln!(f, "    {:>7}    {}", function.instructions, function.name())?;
+            }
+        }
+        writeln!(
+            f,
+            "Statics: {} of {} (RAM {}..{}, {:.1}%)",
+            self.static_count(),
+            STATIC_CAPACITY,
+            STATIC_BASE_ADDRESS,
+            STATIC_BASE_ADDRESS + STATIC_CAPACITY - 1,
+            percent(self.static_count(), STATIC_CAPACITY)
+        )?;
+        for (file, count) in &self.statics {
+            writeln!(f, "    {:>7}  {}", count, file)?;
+        }
+        return Ok(());
+    }
+}
+
+/// Error for assembly generated for `name` that doesn't parse, which would make the
+/// instruction counts and the ROM limit meaningless.
+pub(crate) fn invalid_assembly(file: &str, name: &str, e: AsmError) -> Diagnostic {
+    return Diagnostic::without_span(
+        Severity::Error,
+        file,
+        &format!(
+            "the assembly generated for {} is invalid: line {}: {}",
+            name, e.line, e.message
+        ),
+    );
+}
+
+/// Number of distinct static variables used by `file`.
+fn static_count(file: &VmFile) -> usize {
+    let statics: HashSet<u16> = file
+        .commands
+        .iter()
+        .flat_map(|parsed| match parsed.command {
+            VmCommand::Push { segment, index } | VmCommand::Pop { segment, index } => {
+                vec![(segment, index)]
+            }
+            VmCommand::Move {
+                source,
+                source_index,
+                segment,
+                index,
+            } => vec![(source, source_index), (segment, index)],
+            _ => vec![],
+        })
+        .filter(|(segment, _)| *segment == Segment::Static)
+        .map(|(_, index)| index)
+        .collect();
+    return statics.len();
+}
+
+pub(crate) fn first_file_name(files: &[VmFile]) -> String {
+    return files
+        .first()
+        .map_or(String::new(), |file| format!("{}.vm", file.name));
+}
@@ -1,38 +1,36 @@
-use crate::utils::{create_output_path, search_vm_files, write_to_file};
-use crate::PathType;
-use colored::*;
+use crate::parser::{ArithmeticOp, VmCommand, VmFile};
+use crate::TranslateOptions;
 use std::fmt::Display;
-use std::{
-    fs::File,
-    io::{BufReader, Read},
-    path::{Path, PathBuf},
-};
 
 mod memory;
-use memory::generate_mem_code_block;
+use memory::{generate_mem_code_block, generate_move_code_block, MemCmd};
 
 mod arithmetic_logic;
-use arithmetic_logic::generate_a_l_code_block;
+use arithmetic_logic::{
+    generate_a_l_code_block, generate_optimized_a_l_code_block, generate_shared_comparison_call,
+    generate_shared_comparisons,
+};
 
 mod branching;
-use branching::generate_branching_block;
+use branching::{generate_branching_block, BranchCmd};
 
 mod function;
 use function::generate_function_call;
 use function::generate_function_def;
 use function::generate_function_return;
-
-// use it to store around CODE_BUFFER_LIMIT lines of assembly then write all lines to the output file and empty the vector.
-const CODE_BUFFER_SOFT_LIMIT: usize = 100;
+use function::{
+    generate_shared_call_routines, generate_shared_function_call, generate_shared_function_return,
+    generate_tail_call,
+};
 
 const DEFAULT_PADDING: usize = 4;
 const NO_PADDING: usize = 0;
 
-const SP: usize = 0;
-const LCL: usize = 1;
-const ARG: usize = 2;
-const THIS: usize = 3;
-const THAT: usize = 4;
+pub(crate) const SP: usize = 0;
+pub(crate) const LCL: usize = 1;
+pub(crate) const ARG: usize = 2;
+pub(crate) const THIS: usize = 3;
+pub(crate) const THAT: usize = 4;
 
 const SP_BASE_ADDRESS: usize = 256;
 const LCL_BASE_ADDRESS: usize = 1647;
@@ -44,333 +42,410 @@ fn at<T: Display>(address: T) -> String {
     return format!("@{}", address);
 }
 
-// arithmetic / logical commands:
-const A_L_KEYWORDS: [&'static str; 9] = ["add", "sub", "neg", "eq", "gt", "lt", "and", "or", "not"];
+/// What the bootstrap code at the top of the output sets up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootstrapMode {
+    /// SP, LCL, ARG, THIS and THAT are initialized, then `Sys.init` is called.
+    Full,
+    /// Only SP is initialized; nothing is called.
+    SpOnly,
+    /// No bootstrap code. Used by test scripts that set up the segments themselves.
+    None,
+}
 
-// memory access commands:
-const MEM_KEYWORDS: [&'static str; 2] = ["pop", "push"];
+impl BootstrapMode {
+    pub fn from_name(s: &str) -> Option<BootstrapMode> {
+        return match s {
+            "full" => Some(BootstrapMode::Full),
+            "sp-only" => Some(BootstrapMode::SpOnly),
+            "none" => Some(BootstrapMode::None),
+            _ => None,
+        };
+    }
+
+    /// Picks `Full` if `Sys.init` is defined in one of the files, `None` otherwise.
+    pub fn detect(files: &[VmFile]) -> BootstrapMode {
+        let is_sys_init_defined = files.iter().flat_map(|f| &f.commands).any(|c| {
+            matches!(&c.command, VmCommand::Function { name, .. } if name == "Sys.init")
+        });
+        return if is_sys_init_defined {
+            BootstrapMode::Full
+        } else {
+            BootstrapMode::None
+        };
+    }
+}
 
-// branching keywords:
-const BRANCHING_KEYWORDS: [&'static str; 3] = ["label", "if-goto", "goto"];
+/// How arithmetic and logical commands are translated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArithmeticStrategy {
+    /// Pops the operands into R13/R14, computes there and pushes the result back.
+    #[default]
+    Didactic,
+    /// Computes in place on the top of the stack (e.g. 5 instructions for `add`).
+    Optimized,
+}
 
-// function keywords
-const FUNC_KEYWORDS: [&'static str; 3] = ["return", "function", "call"];
+impl ArithmeticStrategy {
+    pub fn from_name(s: &str) -> Option<ArithmeticStrategy> {
+        return match s {
+            "didactic" => Some(ArithmeticStrategy::Didactic),
+            "optimized" => Some(ArithmeticStrategy::Optimized),
+            _ => None,
+        };
+    }
+}
 
-enum Keyword {
-    FUNC(String),
-    MEM(String),
-    AL(String),
-    BRANCH(String),
+/// Initial values of the segment pointers, set by the bootstrap code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaseAddresses {
+    pub sp: usize,
+    pub lcl: usize,
+    pub arg: usize,
+    pub this: usize,
+    pub that: usize,
 }
 
-impl Keyword {
-    fn from(s: &str) -> Option<Keyword> {
-        if A_L_KEYWORDS.contains(&s) {
-            return Some(Keyword::AL(s.to_string()));
-        } else if MEM_KEYWORDS.contains(&s) {
-            return Some(Keyword::MEM(s.to_string()));
-        } else if FUNC_KEYWORDS.contains(&s) {
-            return Some(Keyword::FUNC(s.to_string()));
-        } else if BRANCHING_KEYWORDS.contains(&s) {
-            return Some(Keyword::BRANCH(s.to_string()));
-        } else {
-            return None;
-        }
+impl Default for BaseAddresses {
+    fn default() -> BaseAddresses {
+        return BaseAddresses {
+            sp: SP_BASE_ADDRESS,
+            lcl: LCL_BASE_ADDRESS,
+            arg: ARG_BASE_ADDRESS,
+            this: THIS_BASE_ADDRESS,
+            that: THAT_BASE_ADDRESS,
+        };
     }
 }
 
-pub struct CodeGenerator<'a> {
-    path_pointer: &'a Path,
-    path_type: &'a PathType,
-    output_file: File,
-    is_debug_option: bool,
-    jump_counter: usize,
+/// ROM words of a program translated with `is_shared_comparisons_option`, and of the same
+/// program with every comparison inlined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SharedComparisonsReport {
+    pub inline_instructions: usize,
+    pub shared_instructions: usize,
 }
 
-impl CodeGenerator<'_> {
-    pub fn new<'a>(
-        path_ref: &'a Path,
-        path_type: &'a PathType,
-        is_debug_option: bool,
-    ) -> CodeGenerator<'a> {
-        let output_path = create_output_path(path_ref, path_type, is_debug_option);
-        println!("Output: {output_path}");
+impl SharedComparisonsReport {
+    /// Negative if the shared routines take more ROM than they save.
+    pub fn saved_instructions(&self) -> i64 {
+        return self.inline_instructions as i64 - self.shared_instructions as i64;
+    }
+}
 
-        let output_file = File::create(&output_path).expect(
-            format!(
-                "[ERROR] couldn't create output file using the following path: {}",
-                output_path,
-            )
-            .as_str(),
-        ); // create asm file.
+pub struct CodeGenerator {
+    is_debug_option: bool,
+    /// `None` detects the mode from the translated files.
+    bootstrap: Option<BootstrapMode>,
+    base_addresses: BaseAddresses,
+    is_global_labels_option: bool,
+    is_shared_calls_option: bool,
+    is_shared_comparisons_option: bool,
+    is_tail_calls_option: bool,
+    arithmetic: ArithmeticStrategy,
+    jump_counter: usize,
+    /// Name of the last `function` command, used to scope labels.
+    current_function: Option<String>,
+}
 
+impl CodeGenerator {
+    pub fn new(options: &TranslateOptions) -> CodeGenerator {
         let jump_counter = 0;
 
         return CodeGenerator {
-            path_pointer: path_ref,
-            path_type,
-            output_file,
-            is_debug_option,
+            is_debug_option: options.is_debug_option,
+            bootstrap: options.bootstrap,
+            base_addresses: options.base_addresses,
+            is_global_labels_option: options.is_global_labels_option,
+            is_shared_calls_option: options.is_shared_calls_option,
+            is_shared_comparisons_option: options.is_shared_comparisons_option,
+            is_tail_calls_option: options.is_tail_calls_option,
+            arithmetic: options.arithmetic,
             jump_counter, // in order to produce unique labels (for GOTOs).
+            current_function: None,
         };
     }
 
-    // ideally only this method and `new` should be exposed
-    pub fn generate_code(&mut self) {
-        let mut files_vec: Vec<PathBuf> = vec![];
-        match &self.path_type {
-            PathType::File => files_vec.push(self.path_pointer.to_path_buf()),
-            PathType::Dir => search_vm_files(self.path_pointer, &mut files_vec),
-        }
-
-        let filename_vec: Vec<String> = (&files_vec)
-            .iter()
-            .map(|p| {
-                p.file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .trim_end_matches(".vm")
-                    .to_string()
-            })
-            .collect();
-        if !filename_vec.contains(&"Sys".to_string()) {
-            println!(
-                "{}",
-                "[WARNING] file Sys.vm does not exist in directory.".purple()
-            );
-        }
-        if !filename_vec.contains(&"Main".to_string()) {
-            println!(
-                "{}",
-                "[WARNING] file Main.vm does not exist in directory.".purple()
-            )
-        }
-
-        for path_buf in &files_vec {
-            self.generate_code_from_file(&path_buf.as_path());
-        }
-    }
-
-    fn generate_code_from_file(&mut self, file_path: &Path) {
-        let mut code_buffer: Vec<String> = vec![];
-        let filename = file_path // get filename and remove extension.
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .trim_end_matches(".vm");
-
-        let file = match File::open(&file_path) {
-            Err(why) => {
-                eprintln!("couldn't open {}: {}", file_path.to_str().unwrap(), why);
-                panic!()
+    /// Mangles a vm label as `Function$label` per the vm specification.
+    ///
+    /// Labels outside any function, or all labels if `is_global_labels_option` is set,
+    /// are kept verbatim.
+    fn scope_label(&self, label: &str) -> String {
+        return match &self.current_function {
+            Some(function_name) if !self.is_global_labels_option => {
+                format!("{}${}", function_name, label)
             }
-            Ok(file) => file,
+            _ => label.to_string(),
         };
+    }
 
-        let mut buf_reader = BufReader::new(file);
-        let mut contents = String::new();
-
-        buf_reader
-            .read_to_string(&mut contents)
-            .expect("couldn't parse content of the input file.");
-
-        let lines: Vec<&str> = contents.split("\n").collect();
+    /// Translates every parsed file and returns the assembly lines of the whole program.
+    pub fn generate_code(&mut self, files: &[VmFile]) -> Vec<String> {
+        return self.generate_code_blocks(files).concat();
+    }
 
-        code_buffer.append(&mut generate_bootstrapping(
+    /// Translates every parsed file and returns one code block per vm command (all files
+    /// in order), preceded by the bootstrap code block (empty if there is no bootstrap).
+    ///
+    /// With `is_shared_calls_option` or `is_shared_comparisons_option`, the first block also
+    /// holds the shared routines.
+    pub fn generate_code_blocks(&mut self, files: &[VmFile]) -> Vec<Vec<String>> {
+        // the bootstrap code is emitted once, at the top of the combined output.
+        let bootstrap = self
+            .bootstrap
+            .unwrap_or_else(|| BootstrapMode::detect(files));
+        let mut first_block = self.generate_shared_routines(files);
+        first_block.append(&mut generate_bootstrapping(
+            bootstrap,
+            &self.base_addresses,
             &mut self.jump_counter,
             self.is_debug_option,
+            self.is_shared_calls_option,
         ));
+        let mut code_blocks: Vec<Vec<String>> = vec![first_block];
+        for file in files {
+            self.generate_code_from_file(file, &mut code_blocks);
+        }
+        return code_blocks;
+    }
 
-        for line in lines {
-            if code_buffer.len() > CODE_BUFFER_SOFT_LIMIT {
-                write_to_file(&mut self.output_file, &mut code_buffer);
-            }
+    /// Emits the shared routines selected by the options, preceded by a jump over them
+    /// so that they can be placed at the top of the program.
+    fn generate_shared_routines(&self, files: &[VmFile]) -> Vec<String> {
+        let mut routines: Vec<String> = vec![];
+        if self.is_shared_calls_option {
+            routines.append(&mut generate_shared_call_routines(self.is_debug_option));
+        }
+        if self.is_shared_comparisons_option {
+            let used: Vec<ArithmeticOp> = files
+                .iter()
+                .flat_map(|f| &f.commands)
+                .filter_map(|c| match c.command {
+                    VmCommand::Arithmetic(a_l_cmd) => Some(a_l_cmd),
+                    _ => None,
+                })
+                .collect();
+            routines.append(&mut generate_shared_comparisons(
+                &used,
+                self.is_debug_option,
+            ));
+        }
+        if routines.is_empty() {
+            return routines;
+        }
 
-            // ignore comments and empty lines.
-            if line.trim() == "" || line.starts_with("//") {
+        let end_label = "$$SHARED_END";
+        let mut code_block: Vec<String> = vec![at(end_label), "0; JMP".to_string()];
+        code_block.append(&mut routines);
+        code_block.push(format!("({end_label})"));
+        return code_block;
+    }
+
+    fn generate_code_from_file(&mut self, file: &VmFile, code_blocks: &mut Vec<Vec<String>>) {
+        self.current_function = None;
+        let mut is_tail_call = false;
+        for (i, parsed) in file.commands.iter().enumerate() {
+            // the `return` of a tail call is never reached, its code block is empty.
+            if is_tail_call {
+                is_tail_call = false;
+                code_blocks.push(vec![]);
                 continue;
             }
-
-            let line = line.trim_end_matches("\r").trim_end_matches("\n"); // remove extra characters such as \r and \n.
-
-            let mut line_vec: Vec<&str> = line.split(" ").collect(); // split line in words (operators and arguments) and store them in a vector.
-
-            match Keyword::from(&line_vec[0]) {
-                Some(keyword) => match keyword {
-                    Keyword::AL(a_l_cmd) => {
-                        let mut a_l_code_block = generate_a_l_code_block(
-                            &a_l_cmd,
-                            filename,
-                            &mut self.jump_counter,
-                            self.is_debug_option,
-                            NO_PADDING,
-                        );
-
-                        code_buffer.append(&mut a_l_code_block);
-                    }
-                    Keyword::MEM(mem_cmd) => {
-                        if line_vec.len() < 3 {
-                            eprintln!("[ERROR] bad syntax. Line includes memory access operator but doesn't include one of the following arguments: a memory segment or an index.");
-                            panic!();
-                        }
-
-                        let mem_segment = line_vec[1];
-
-                        line_vec[2] = line_vec[2].trim_end_matches(|c| !char::is_numeric(c));
-
-                        let mem_index: usize = match line_vec[2].parse() {
-                            Ok(i) => i,
-                            Err(_) => {
-                                eprintln!(
-                                    "[ERROR] bad syntax. Index {} cannot be parsed as an unsigned integer.",
-                                    line_vec[2]
-                                );
-                                eprintln!("{:#?}", line_vec);
-                                panic!();
-                            }
-                        };
-
-                        let mut code_block = generate_mem_code_block(
-                            &mem_cmd,
-                            mem_segment,
-                            mem_index,
-                            filename,
-                            self.is_debug_option,
-                            NO_PADDING,
-                        );
-                        code_buffer.append(&mut code_block);
-                    }
-                    Keyword::BRANCH(branch_cmd) => {
-                        // expected: label <str> or if-goto <str> or goto <str>
-                        if line_vec.len() < 2 {
-                            eprintln!("[ERROR] bad syntax. Line includes branching keyword but does not include a label.");
-                            panic!();
-                        }
-
-                        let goto_label = line_vec[1];
-
-                        let mut code_block = generate_branching_block(
-                            &branch_cmd,
-                            goto_label,
-                            filename,
-                            self.is_debug_option,
-                            NO_PADDING,
-                        );
-                        code_buffer.append(&mut code_block);
-                    }
-                    Keyword::FUNC(func_keyword) => {
-                        match func_keyword.as_str() {
-                            "function" | "call" => {
-                                if line_vec.len() < 3 {
-                                    eprintln!("[ERROR] bad syntax. Expected two arguments for function call or definition.");
-                                    panic!()
-                                }
-                                let function_name = line_vec[1];
-                                if self.is_debug_option {
-                                    let comment = format!(
-                                        "\n// {} {} {}",
-                                        func_keyword, function_name, line_vec[2]
-                                    ); // line_vec[2] = n_args or n_vars
-                                    code_buffer.push(comment);
-                                }
-
-                                let mut code_block = if func_keyword == "function" {
-                                    let n_vars: usize = match line_vec[2].parse::<usize>() {
-                                        Ok(n) => n,
-                                        Err(e) => {
-                                            eprintln!("{e}");
-                                            panic!()
-                                        }
-                                    };
-
-                                    generate_function_def(
-                                        function_name,
-                                        n_vars,
-                                        filename,
-                                        self.is_debug_option,
-                                    )
-                                } else {
-                                    let n_args: usize = match line_vec[2].parse::<usize>() {
-                                        Ok(n) => n,
-                                        Err(e) => {
-                                            eprintln!("{e}");
-                                            panic!()
-                                        }
-                                    };
-
-                                    generate_function_call(
-                                        function_name,
-                                        n_args,
-                                        filename,
-                                        &mut self.jump_counter,
-                                        self.is_debug_option,
-                                    )
-                                };
-                                code_buffer.append(&mut code_block);
-                            }
-                            "return" => {
-                                if self.is_debug_option {
-                                    let comment = format!("\n// {}", func_keyword);
-                                    code_buffer.push(comment);
-                                }
-                                code_buffer.append(&mut generate_function_return(
-                                    filename,
-                                    &mut self.jump_counter,
-                                    self.is_debug_option,
-                                ));
-                            }
-                            _ => {
-                                // should not be reachable
-                                eprintln!("[ERROR] unkown error.");
-                                panic!();
-                            }
-                        }
-                    }
-                },
-                None => {
-                    eprintln!("[ERROR] bad syntax. Line starts with unkown keyword.");
-                    panic!();
+            // top-level code has no frame to reuse.
+            is_tail_call = self.is_tail_calls_option
+                && self.current_function.is_some()
+                && matches!(parsed.command, VmCommand::Call { .. })
+                && matches!(
+                    file.commands.get(i + 1).map(|next| &next.command),
+                    Some(VmCommand::Return)
+                );
+            match &parsed.command {
+                VmCommand::Call { name, n_args } if is_tail_call => {
+                    code_blocks.push(generate_tail_call(
+                        name,
+                        usize::from(*n_args),
+                        &mut self.jump_counter,
+                        self.is_debug_option,
+                    ));
                 }
+                command => code_blocks.push(self.generate_command(command, &file.name)),
             }
         }
+    }
 
-        if code_buffer.len() > 0 {
-            write_to_file(&mut self.output_file, &mut code_buffer);
+    /// Translates a single vm command.
+    fn generate_command(&mut self, command: &VmCommand, filename: &str) -> Vec<String> {
+        let mut code_block: Vec<String> = vec![];
+        match command {
+            VmCommand::Arithmetic(
+                a_l_cmd @ (ArithmeticOp::Eq | ArithmeticOp::Gt | ArithmeticOp::Lt),
+            ) if self.is_shared_comparisons_option => {
+                code_block = generate_shared_comparison_call(
+                    *a_l_cmd,
+                    &mut self.jump_counter,
+                    self.is_debug_option,
+                );
+            }
+            VmCommand::Arithmetic(a_l_cmd) if self.arithmetic == ArithmeticStrategy::Optimized => {
+                code_block = generate_optimized_a_l_code_block(
+                    *a_l_cmd,
+                    &mut self.jump_counter,
+                    self.is_debug_option,
+                );
+            }
+            VmCommand::Arithmetic(a_l_cmd) => {
+                code_block = generate_a_l_code_block(
+                    *a_l_cmd,
+                    filename,
+                    &mut self.jump_counter,
+                    self.is_debug_option,
+                    NO_PADDING,
+                );
+            }
+            VmCommand::Push { segment, index } | VmCommand::Pop { segment, index } => {
+                let mem_cmd = if matches!(command, VmCommand::Push { .. }) {
+                    MemCmd::Push
+                } else {
+                    MemCmd::Pop
+                };
+                code_block = generate_mem_code_block(
+                    mem_cmd,
+                    *segment,
+                    usize::from(*index),
+                    filename,
+                    self.is_debug_option,
+                    NO_PADDING,
+                );
+            }
+            VmCommand::Move {
+                source,
+                source_index,
+                segment,
+                index,
+            } => {
+                code_block = generate_move_code_block(
+                    *source,
+                    usize::from(*source_index),
+                    *segment,
+                    usize::from(*index),
+                    filename,
+                    self.is_debug_option,
+                );
+            }
+            VmCommand::Label(goto_label)
+            | VmCommand::Goto(goto_label)
+            | VmCommand::IfGoto(goto_label) => {
+                let branch_cmd = match command {
+                    VmCommand::Label(_) => BranchCmd::Label,
+                    VmCommand::Goto(_) => BranchCmd::Goto,
+                    _ => BranchCmd::IfGoto,
+                };
+                code_block = generate_branching_block(
+                    branch_cmd,
+                    &self.scope_label(goto_label),
+                    filename,
+                    self.is_debug_option,
+                    NO_PADDING,
+                );
+            }
+            VmCommand::Function { name, n_vars } => {
+                self.current_function = Some(name.clone());
+                if self.is_debug_option {
+                    code_block.push(format!("\n// {}", command));
+                }
+                code_block.append(&mut generate_function_def(
+                    name,
+                    usize::from(*n_vars),
+                    filename,
+                    self.is_debug_option,
+                ));
+            }
+            VmCommand::Call { name, n_args } => {
+                if self.is_debug_option {
+                    code_block.push(format!("\n// {}", command));
+                }
+                if self.is_shared_calls_option {
+                    code_block.append(&mut generate_shared_function_call(
+                        name,
+                        usize::from(*n_args),
+                        &mut self.jump_counter,
+                    ));
+                } else {
+                    code_block.append(&mut generate_function_call(
+                        name,
+                        usize::from(*n_args),
+                        filename,
+                        &mut self.jump_counter,
+                        self.is_debug_option,
+                    ));
+                }
+            }
+            VmCommand::Return => {
+                if self.is_debug_option {
+                    code_block.push(format!("\n// {}", command));
+                }
+                if self.is_shared_calls_option {
+                    code_block.append(&mut generate_shared_function_return());
+                } else {
+                    code_block.append(&mut generate_function_return(
+                        filename,
+                        &mut self.jump_counter,
+                        self.is_debug_option,
+                    ));
+                }
+            }
         }
+        return code_block;
     }
 }
 
-fn generate_bootstrapping(jump_counter_ref: &mut usize, is_debug_option: bool) -> Vec<String> {
-    let mut code_block: Vec<String> = vec![
-        at(SP_BASE_ADDRESS),
-        "D = A".to_string(),
-        at(SP),
-        "M = D".to_string(),
-        at(LCL_BASE_ADDRESS),
-        "D = A".to_string(),
-        at(LCL),
-        "M = D".to_string(),
-        at(ARG_BASE_ADDRESS),
-        "D = A".to_string(),
-        at(ARG),
-        "M = D".to_string(),
-        at(THIS_BASE_ADDRESS),
-        "D = A".to_string(),
-        at(THIS),
-        "M = D".to_string(),
-        at(THAT_BASE_ADDRESS),
-        "D = A".to_string(),
-        at(THAT),
-        "M = D".to_string(),
+fn generate_bootstrapping(
+    bootstrap: BootstrapMode,
+    base_addresses: &BaseAddresses,
+    jump_counter_ref: &mut usize,
+    is_debug_option: bool,
+    is_shared_calls_option: bool,
+) -> Vec<String> {
+    let mut code_block: Vec<String> = vec![];
+    if bootstrap == BootstrapMode::None {
+        return code_block;
+    }
+
+    let pointers = [
+        (SP, base_addresses.sp),
+        (LCL, base_addresses.lcl),
+        (ARG, base_addresses.arg),
+        (THIS, base_addresses.this),
+        (THAT, base_addresses.that),
     ];
-    code_block.append(&mut generate_function_call(
-        "Sys.init",
-        0,
-        "Sys",
-        jump_counter_ref,
-        is_debug_option,
-    ));
+    let n_pointers = if bootstrap == BootstrapMode::Full {
+        pointers.len()
+    } else {
+        1
+    };
+    for (pointer, base_address) in &pointers[..n_pointers] {
+        code_block.append(&mut vec![
+            at(base_address),
+            "D = A".to_string(),
+            at(pointer),
+            "M = D".to_string(),
+        ]);
+    }
+
+    if bootstrap == BootstrapMode::Full && is_shared_calls_option {
+        code_block.append(&mut generate_shared_function_call(
+            "Sys.init",
+            0,
+            jump_counter_ref,
+        ));
+    } else if bootstrap == BootstrapMode::Full {
+        code_block.append(&mut generate_function_call(
+            "Sys.init",
+            0,
+            "Sys",
+            jump_counter_ref,
+            is_debug_option,
+        ));
+    }
     return code_block;
 }
@@ -1,9 +1,10 @@
+use crate::parser::{ArithmeticOp, Segment};
 use crate::utils::add_padding;
 
-use super::{at, generate_mem_code_block, DEFAULT_PADDING};
+use super::{at, generate_mem_code_block, MemCmd, DEFAULT_PADDING};
 
 pub fn generate_a_l_code_block(
-    a_l_cmd: &str,
+    a_l_cmd: ArithmeticOp,
     filename: &str,
     jump_counter_ref: &mut usize,
     is_debug_option: bool,
@@ -14,14 +15,14 @@ pub fn generate_a_l_code_block(
     *jump_counter_ref += 1;
 
     if is_debug_option {
-        let comment = format!("// {}", a_l_cmd);
+        let comment = format!("// {}", a_l_cmd.name());
         code_block.push("\n".to_string());
         code_block.push(comment);
     }
 
     let mut pop1 = generate_mem_code_block(
-        "pop",
-        "general",
+        MemCmd::Pop,
+        Segment::General,
         13,
         filename,
         is_debug_option,
@@ -30,10 +31,10 @@ pub fn generate_a_l_code_block(
     code_block.append(&mut pop1);
 
     // `neg` and `not` operate on one value only, so there is no need for popping a second value from the stack
-    if a_l_cmd != "neg" && a_l_cmd != "not" {
+    if !a_l_cmd.is_unary() {
         let mut pop2 = generate_mem_code_block(
-            "pop",
-            "general",
+            MemCmd::Pop,
+            Segment::General,
             14,
             filename,
             is_debug_option,
@@ -49,49 +50,49 @@ pub fn generate_a_l_code_block(
     let label_if_true = format!("true_expression{}", *jump_counter_ref);
     let label_if_false = format!("false_expression{}", *jump_counter_ref);
     match a_l_cmd {
-        "add" => {
+        ArithmeticOp::Add => {
             temp_vec = vec![
                 at(14),                  // go to temp 2
                 "D = D + M".to_string(), // D  = D + value of temp2
                 "M = D".to_string(),     // replace temp 2 with the value of temp2+temp1
             ];
         }
-        "sub" => {
+        ArithmeticOp::Sub => {
             temp_vec = vec![
                 at(14),                  // go to temp 2
                 "D = M - D".to_string(), // D  = temp2 - temp1
                 "M = D".to_string(),     // replace temp 2 with the value of temp2 - temp1
             ];
         }
-        "neg" => {
+        ArithmeticOp::Neg => {
             temp_vec = vec![
                 "D = -D".to_string(), // D  = - temp1
                 at(14),               // go to temp 2
                 "M = D".to_string(),  // replace temp 2 with the value of -temp1
             ];
         }
-        "and" => {
+        ArithmeticOp::And => {
             temp_vec = vec![
                 at(14),                  // go to temp 2
                 "D = M & D".to_string(), // D  = temp2 & temp1
                 "M = D".to_string(),     // replace temp 2 with the value of temp2 & temp1
             ];
         }
-        "or" => {
+        ArithmeticOp::Or => {
             temp_vec = vec![
                 at(14),                  // go to temp 2
                 "D = M | D".to_string(), // D  = temp2 | temp1
                 "M = D".to_string(),     // replace temp 2 with the value of temp2 | temp1
             ];
         }
-        "not" => {
+        ArithmeticOp::Not => {
             temp_vec = vec![
                 "D = !D".to_string(), // D = !temp1
                 at(14),               // go to temp 2
                 "M = D".to_string(),  // replace temp 2 with the value of !temp1
             ];
         }
-        "eq" => {
+        ArithmeticOp::Eq => {
             temp_vec = vec![
                 at(14),                  // go to temp 2
                 "D = M - D".to_string(), // D  = temp2 - temp1 (order doesn't matter since we're checking for inequality with 0)
@@ -107,44 +108,39 @@ pub fn generate_a_l_code_block(
                 format!("({label_if_false})"),
             ];
         }
-        "gt" | "lt" => {
-            code_block.push(at(14));
-
-            if a_l_cmd == "lt" {
-                code_block.push("D = D - M".to_string());
-            // D  = temp1 - temp2 (if D>0 then temp2<temp1)
+        ArithmeticOp::Gt | ArithmeticOp::Lt => {
+            // gt tests D>0 and lt tests D<0, D having the sign of temp2 - temp1.
+            let jump = if a_l_cmd == ArithmeticOp::Lt {
+                "D;JLT"
             } else {
-                code_block.push("D = M - D".to_string());
-                // D  = temp2 - temp1 (if D>0 then temp2>temp1)
-            }
-
-            // the rest is the same
-            temp_vec = vec![
+                "D;JGT"
+            };
+            temp_vec = signed_difference(
+                &[at(14)],
+                &[at(13)],
+                &format!("y_negative{}", *jump_counter_ref),
+                &format!("same_sign{}", *jump_counter_ref),
+                &format!("compared{}", *jump_counter_ref),
+            );
+            temp_vec.append(&mut vec![
                 at(&label_if_true),
-                "D;JGT".to_string(), // jump to (greater) only if D>0
-                at(14),              // go to temp 2 (this code will be executed if NOT[D > 0])
+                jump.to_string(),    // jump to (true) only if the comparison holds
+                at(14),              // go to temp 2 (this code will be executed if it doesn't)
                 "M = 0".to_string(), // set temp2 to false
                 at(&label_if_false),
                 "0;JMP".to_string(),
                 format!("({label_if_true})"),
                 at(14),               // go to temp 2
-                "M = -1".to_string(), // set temp2 to true since D>0
+                "M = -1".to_string(), // set temp2 to true
                 format!("({label_if_false})"),
-            ];
-        }
-        _ => {
-            eprintln!(
-                "[ERROR] {} cannot be cannot be recognized as an arithmetic or logical command.",
-                a_l_cmd
-            );
-            panic!();
+            ]);
         }
     }
     code_block.append(&mut temp_vec);
 
     let mut push_temp_2 = generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         14,
         filename,
         is_debug_option,
@@ -159,3 +155,218 @@ pub fn generate_a_l_code_block(
     };
     return code_block;
 }
+
+/// Translates an arithmetic or logical command in place on the stack top (the
+/// `ArithmeticStrategy::Optimized` strategy): binary commands pop `y` into D and
+/// overwrite `x`, unary commands overwrite the top of the stack.
+///
+/// Comparisons test the sign of `signed_difference` like `generate_a_l_code_block`.
+pub fn generate_optimized_a_l_code_block(
+    a_l_cmd: ArithmeticOp,
+    jump_counter_ref: &mut usize,
+    is_debug_option: bool,
+) -> Vec<String> {
+    let mut code_block: Vec<String> = vec![];
+
+    *jump_counter_ref += 1;
+
+    if is_debug_option {
+        code_block.push("\n".to_string());
+        code_block.push(format!("// {}", a_l_cmd.name()));
+    }
+
+    if a_l_cmd.is_unary() {
+        let comp = if a_l_cmd == ArithmeticOp::Neg {
+            "-M"
+        } else {
+            "!M"
+        };
+        code_block.append(&mut vec![
+            at("SP"),
+            "A = M - 1".to_string(), // A = address of the top of the stack
+            format!("M = {comp}"),
+        ]);
+        return code_block;
+    }
+
+    code_block.append(&mut vec![
+        at("SP"),
+        "AM = M - 1".to_string(), // SP--, A = address of y
+        "D = M".to_string(),      // D = y
+        "A = A - 1".to_string(),  // A = address of x
+    ]);
+    let comp = match a_l_cmd {
+        ArithmeticOp::Add => Some("D + M"),
+        ArithmeticOp::Sub => Some("M - D"),
+        ArithmeticOp::And => Some("D & M"),
+        ArithmeticOp::Or => Some("D | M"),
+        _ => None,
+    };
+    if let Some(comp) = comp {
+        code_block.push(format!("M = {comp}")); // x = x op y
+        return code_block;
+    }
+
+    let jump = match a_l_cmd {
+        ArithmeticOp::Eq => "JEQ",
+        ArithmeticOp::Gt => "JGT",
+        _ => "JLT",
+    };
+
+    let label_if_true = format!("true_expression{}", *jump_counter_ref);
+    if a_l_cmd == ArithmeticOp::Eq {
+        code_block.push("D = M - D".to_string()); // D = x - y
+    } else {
+        code_block.append(&mut signed_difference(
+            &[at("SP"), "A = M - 1".to_string()],
+            &[at("SP"), "A = M".to_string()],
+            &format!("y_negative{}", *jump_counter_ref),
+            &format!("same_sign{}", *jump_counter_ref),
+            &format!("compared{}", *jump_counter_ref),
+        ));
+        code_block.append(&mut vec![at("SP"), "A = M - 1".to_string()]);
+    }
+    code_block.append(&mut vec![
+        "M = -1".to_string(), // x = true
+        at(&label_if_true),
+        format!("D; {jump}"),
+        at("SP"),
+        "A = M - 1".to_string(),
+        "M = 0".to_string(), // x = false
+        format!("({label_if_true})"),
+    ]);
+    return code_block;
+}
+
+/// Sets D to a value with the sign of `x - y` for the signed values x and y, without
+/// the overflow of `x - y` when their signs differ (e.g. `32767 - (-1)`): D = -1 if
+/// `x < 0 <= y`, D = 1 if `y < 0 <= x`, else D = x - y.
+///
+/// Expects D = y. `load_x` and `load_y` set A to the address of x and y. The labels
+/// must be unique.
+fn signed_difference(
+    load_x: &[String],
+    load_y: &[String],
+    y_negative: &str,
+    same_sign: &str,
+    compared: &str,
+) -> Vec<String> {
+    let mut code_block: Vec<String> = vec![at(y_negative), "D; JLT".to_string()];
+    code_block.extend_from_slice(load_x);
+    code_block.append(&mut vec![
+        "D = M".to_string(), // D = x, y >= 0
+        at(same_sign),
+        "D; JGE".to_string(),
+        "D = -1".to_string(), // x < 0 <= y
+        at(compared),
+        "0; JMP".to_string(),
+        format!("({y_negative})"),
+    ]);
+    code_block.extend_from_slice(load_x);
+    code_block.append(&mut vec![
+        "D = M".to_string(), // D = x, y < 0
+        at(same_sign),
+        "D; JLT".to_string(),
+        "D = 1".to_string(), // y < 0 <= x
+        at(compared),
+        "0; JMP".to_string(),
+        format!("({same_sign})"),
+    ]);
+    code_block.extend_from_slice(load_y);
+    code_block.append(&mut vec![
+        "D = D - M".to_string(), // D = x - y, which can't overflow
+        format!("({compared})"),
+    ]);
+    return code_block;
+}
+
+/// R13: return address passed to the shared comparison routines.
+const R_RETURN_ADDRESS: usize = 13;
+
+/// Label of the shared routine for `eq`, `gt` or `lt`.
+fn shared_comparison_label(a_l_cmd: ArithmeticOp) -> String {
+    return format!("$${}", a_l_cmd.name().to_uppercase());
+}
+
+/// Emits the `$$EQ`, `$$GT` and `$$LT` routines shared by every comparison when
+/// `is_shared_comparisons_option` is set, for the comparisons in `used`. Each one replaces
+/// the two values on top of the stack with the result, then jumps to the return address
+/// stored in R13.
+pub fn generate_shared_comparisons(used: &[ArithmeticOp], is_debug_option: bool) -> Vec<String> {
+    let mut code_block: Vec<String> = vec![];
+    for (a_l_cmd, jump) in [
+        (ArithmeticOp::Eq, "JEQ"),
+        (ArithmeticOp::Gt, "JGT"),
+        (ArithmeticOp::Lt, "JLT"),
+    ] {
+        if !used.contains(&a_l_cmd) {
+            continue;
+        }
+        let label = shared_comparison_label(a_l_cmd);
+        if is_debug_option {
+            code_block.push(format!(
+                "\n// shared {} routine: R13 = return address",
+                a_l_cmd.name()
+            ));
+        }
+        code_block.append(&mut vec![
+            format!("({label})"),
+            at("SP"),
+            "AM = M - 1".to_string(), // SP--, A = address of y
+            "D = M".to_string(),      // D = y
+            "A = A - 1".to_string(),  // A = address of x
+        ]);
+        if a_l_cmd == ArithmeticOp::Eq {
+            code_block.push("D = M - D".to_string()); // D = x - y
+        } else {
+            code_block.append(&mut signed_difference(
+                &[at("SP"), "A = M - 1".to_string()],
+                &[at("SP"), "A = M".to_string()],
+                &format!("{label}_Y_NEGATIVE"),
+                &format!("{label}_SAME_SIGN"),
+                &format!("{label}_COMPARED"),
+            ));
+            code_block.append(&mut vec![at("SP"), "A = M - 1".to_string()]);
+        }
+        code_block.append(&mut vec![
+            "M = -1".to_string(), // x = true
+            at(format!("{label}_TRUE")),
+            format!("D; {jump}"),
+            at("SP"),
+            "A = M - 1".to_string(),
+            "M = 0".to_string(), // x = false
+            format!("({label}_TRUE)"),
+            at(R_RETURN_ADDRESS),
+            "A = M".to_string(),
+            "0; JMP".to_string(), // jump to return address
+        ]);
+    }
+    return code_block;
+}
+
+/// `eq`, `gt` or `lt` using the shared comparison routines.
+pub fn generate_shared_comparison_call(
+    a_l_cmd: ArithmeticOp,
+    jump_counter_ref: &mut usize,
+    is_debug_option: bool,
+) -> Vec<String> {
+    *jump_counter_ref += 1;
+    let label = shared_comparison_label(a_l_cmd);
+    let return_label = format!("{}_ret_{}", label, *jump_counter_ref);
+
+    let mut code_block: Vec<String> = vec![];
+    if is_debug_option {
+        code_block.push("\n".to_string());
+        code_block.push(format!("// {}", a_l_cmd.name()));
+    }
+    code_block.append(&mut vec![
+        at(&return_label),
+        "D = A".to_string(),
+        at(R_RETURN_ADDRESS),
+        "M = D".to_string(),
+        at(&label),
+        "0; JMP".to_string(),
+        format!("({return_label})"),
+    ]);
+    return code_block;
+}
@@ -1,13 +1,32 @@
+use crate::parser::Segment;
 use crate::utils::add_padding;
 
-use super::{at, generate_mem_code_block, DEFAULT_PADDING};
+use super::{at, generate_mem_code_block, MemCmd, DEFAULT_PADDING};
 
-/// Assumes that `goto_label` is unique accross all vm files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchCmd {
+    Label,
+    Goto,
+    IfGoto,
+}
+
+impl BranchCmd {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            BranchCmd::Label => "label",
+            BranchCmd::Goto => "goto",
+            BranchCmd::IfGoto => "if-goto",
+        };
+    }
+}
+
+/// Assumes that `goto_label` is unique accross all vm files
+/// (see `CodeGenerator::scope_label`).
 ///
 /// When `if-goto` is used, it is expected that a boolean value is pushed on the stack.
 /// The author of the vm code is responsible for ensuring that said condition is true.
 pub fn generate_branching_block(
-    branch_keyword: &str,
+    branch_keyword: BranchCmd,
     goto_label: &str,
     filename: &str,
     is_debug_option: bool,
@@ -16,24 +35,24 @@ pub fn generate_branching_block(
     let mut code_block: Vec<String> = vec![];
 
     if is_debug_option {
-        let comment = format!("// {} {}", branch_keyword, goto_label);
+        let comment = format!("// {} {}", branch_keyword.name(), goto_label);
         code_block.push("\n".to_string());
         code_block.push(comment);
     }
 
-    let unique_label = format!("{}", goto_label);
+    let unique_label = goto_label.to_string();
     match branch_keyword {
-        "label" => {
+        BranchCmd::Label => {
             code_block.push(format!("({})", unique_label));
         }
-        "goto" => {
+        BranchCmd::Goto => {
             code_block.push(at(unique_label));
             code_block.push("0;JMP".to_string());
         }
-        "if-goto" => {
+        BranchCmd::IfGoto => {
             code_block.append(&mut generate_mem_code_block(
-                "pop",
-                "general",
+                MemCmd::Pop,
+                Segment::General,
                 13,
                 filename,
                 is_debug_option,
@@ -44,13 +63,6 @@ pub fn generate_branching_block(
             code_block.push(at(unique_label));
             code_block.push("D;JNE".to_string());
         }
-        _ => {
-            eprintln!(
-                "[ERROR] keyword {} cannot be recognized as a branching command.",
-                branch_keyword
-            );
-            panic!();
-        }
     }
     code_block = if is_debug_option && padding != 0 {
         code_block.iter().map(|s| add_padding(s, padding)).collect()
@@ -1,8 +1,13 @@
 use std::vec;
 
+use crate::parser::{ArithmeticOp, Segment};
+
 use super::{
-    arithmetic_logic::generate_a_l_code_block, at, branching::generate_branching_block,
-    memory::generate_mem_code_block, DEFAULT_PADDING, SP,
+    arithmetic_logic::generate_a_l_code_block,
+    at,
+    branching::{generate_branching_block, BranchCmd},
+    memory::{generate_mem_code_block, MemCmd},
+    DEFAULT_PADDING, SP,
 };
 
 // The last 3 registers in the `temp` segment are used by the vm translator
@@ -29,8 +34,8 @@ pub fn generate_function_def(
 
     for _ in 0..n_vars {
         code_block.append(&mut generate_mem_code_block(
-            "push",
-            "constant",
+            MemCmd::Push,
+            Segment::Constant,
             0,
             filename,
             is_debug_option,
@@ -67,32 +72,32 @@ pub fn generate_function_call(
     ]);
 
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         1,
         filename,
         is_debug_option,
         DEFAULT_PADDING,
     )); // push LCL
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         2,
         filename,
         is_debug_option,
         DEFAULT_PADDING,
     )); // push ARG
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         3,
         filename,
         is_debug_option,
         DEFAULT_PADDING,
     )); // push THIS
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         4,
         filename,
         is_debug_option,
@@ -100,8 +105,8 @@ pub fn generate_function_call(
     )); // push THAT
 
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         0,
         filename,
         is_debug_option,
@@ -110,8 +115,8 @@ pub fn generate_function_call(
 
     let x: usize = n_args + 5;
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "constant",
+        MemCmd::Push,
+        Segment::Constant,
         x,
         filename,
         is_debug_option,
@@ -119,15 +124,15 @@ pub fn generate_function_call(
     ));
 
     code_block.append(&mut generate_a_l_code_block(
-        "sub",
+        ArithmeticOp::Sub,
         filename,
         jump_counter_ref,
         is_debug_option,
         DEFAULT_PADDING,
     )); // on top of the stack: SP - (n_args+5)
     code_block.append(&mut generate_mem_code_block(
-        "pop",
-        "general",
+        MemCmd::Pop,
+        Segment::General,
         2,
         filename,
         is_debug_option,
@@ -136,16 +141,16 @@ pub fn generate_function_call(
 
     // LCL = SP
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         0,
         filename,
         is_debug_option,
         DEFAULT_PADDING,
     ));
     code_block.append(&mut generate_mem_code_block(
-        "pop",
-        "general",
+        MemCmd::Pop,
+        Segment::General,
         1,
         filename,
         is_debug_option,
@@ -153,7 +158,7 @@ pub fn generate_function_call(
     ));
 
     code_block.append(&mut generate_branching_block(
-        "goto",
+        BranchCmd::Goto,
         function_name,
         filename,
         is_debug_option,
@@ -175,8 +180,8 @@ pub fn generate_function_return(
 
     // push LCL
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         1,
         filename,
         is_debug_option,
@@ -184,8 +189,8 @@ pub fn generate_function_return(
     ));
 
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "constant",
+        MemCmd::Push,
+        Segment::Constant,
         5,
         filename,
         is_debug_option,
@@ -194,7 +199,7 @@ pub fn generate_function_return(
 
     // leaves (LCL-5) on top of the stack
     code_block.append(&mut generate_a_l_code_block(
-        "sub",
+        ArithmeticOp::Sub,
         filename,
         jump_counter_ref,
         is_debug_option,
@@ -203,8 +208,8 @@ pub fn generate_function_return(
 
     // RAM[TEMP_Y] = LCL-5 = pointer to return address
     code_block.append(&mut generate_mem_code_block(
-        "pop",
-        "general",
+        MemCmd::Pop,
+        Segment::General,
         TEMP_Y,
         filename,
         is_debug_option,
@@ -222,8 +227,8 @@ pub fn generate_function_return(
 
     // push LCL
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         1,
         filename,
         is_debug_option,
@@ -232,8 +237,8 @@ pub fn generate_function_return(
 
     // pop endframe address (equivalent to LCL)
     code_block.append(&mut generate_mem_code_block(
-        "pop",
-        "general",
+        MemCmd::Pop,
+        Segment::General,
         TEMP_X,
         filename,
         is_debug_option,
@@ -242,8 +247,8 @@ pub fn generate_function_return(
 
     // *(ARG+0) = return value (is on top of the stack)
     code_block.append(&mut generate_mem_code_block(
-        "pop",
-        "argument",
+        MemCmd::Pop,
+        Segment::Argument,
         0,
         filename,
         is_debug_option,
@@ -252,8 +257,8 @@ pub fn generate_function_return(
 
     // *SP = ARG
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         2,
         filename,
         is_debug_option,
@@ -262,8 +267,8 @@ pub fn generate_function_return(
 
     // *SP = 1
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "constant",
+        MemCmd::Push,
+        Segment::Constant,
         1,
         filename,
         is_debug_option,
@@ -272,7 +277,7 @@ pub fn generate_function_return(
 
     // *SP = ARG + 1
     code_block.append(&mut generate_a_l_code_block(
-        "add",
+        ArithmeticOp::Add,
         filename,
         jump_counter_ref,
         is_debug_option,
@@ -281,8 +286,8 @@ pub fn generate_function_return(
 
     // RAM[TEMP_Z] = ARG+1
     code_block.append(&mut generate_mem_code_block(
-        "pop",
-        "general",
+        MemCmd::Pop,
+        Segment::General,
         TEMP_Z,
         filename,
         is_debug_option,
@@ -298,8 +303,8 @@ pub fn generate_function_return(
 
         // push endframe address that was previously saved in RAM[TEMP_X] (see above)
         code_block.append(&mut generate_mem_code_block(
-            "push",
-            "general",
+            MemCmd::Push,
+            Segment::General,
             TEMP_X,
             filename,
             is_debug_option,
@@ -308,8 +313,8 @@ pub fn generate_function_return(
 
         // *SP = n
         code_block.append(&mut generate_mem_code_block(
-            "push",
-            "constant",
+            MemCmd::Push,
+            Segment::Constant,
             n,
             filename,
             is_debug_option,
@@ -318,7 +323,7 @@ pub fn generate_function_return(
 
         // *SP = endframe address - n
         code_block.append(&mut generate_a_l_code_block(
-            "sub",
+            ArithmeticOp::Sub,
             filename,
             jump_counter_ref,
             is_debug_option,
@@ -338,8 +343,8 @@ pub fn generate_function_return(
 
         // replaces the current segment pointer with the base address of the old segment
         code_block.append(&mut generate_mem_code_block(
-            "pop",
-            "general",
+            MemCmd::Pop,
+            Segment::General,
             5 - n,
             filename,
             is_debug_option,
@@ -349,8 +354,8 @@ pub fn generate_function_return(
 
     // *SP = new sp stored in RAM[TEMP_Z]
     code_block.append(&mut generate_mem_code_block(
-        "push",
-        "general",
+        MemCmd::Push,
+        Segment::General,
         TEMP_Z,
         filename,
         is_debug_option,
@@ -360,8 +365,8 @@ pub fn generate_function_return(
     // destroy stack
     // RAM[0] = SP = ARG+1
     code_block.append(&mut generate_mem_code_block(
-        "pop",
-        "general",
+        MemCmd::Pop,
+        Segment::General,
         0,
         filename,
         is_debug_option,
@@ -374,3 +379,240 @@ pub fn generate_function_return(
     code_block.push("0; JMP".to_string());
     return code_block;
 }
+
+/// Label of the shared call routine, see `generate_shared_routines`.
+const SHARED_CALL_LABEL: &str = "$$CALL";
+/// Label of the shared return routine, see `generate_shared_routines`.
+const SHARED_RETURN_LABEL: &str = "$$RETURN";
+
+/// R13: return address passed to `$$CALL`, then endframe address in `$$RETURN`.
+const R_RETURN_ADDRESS: usize = 13;
+/// R14: address of the called function, then return address in `$$RETURN`.
+const R_FUNCTION: usize = 14;
+/// R15: number of arguments passed to `$$CALL`.
+const R_N_ARGS: usize = 15;
+
+/// Emits the `$$CALL` and `$$RETURN` routines shared by every call site and `return`
+/// when `is_shared_calls_option` is set.
+pub fn generate_shared_call_routines(is_debug_option: bool) -> Vec<String> {
+    let mut code_block: Vec<String> = vec![];
+
+    if is_debug_option {
+        code_block.push(
+            "\n// shared call routine: R13 = return address, R14 = function, R15 = n_args"
+                .to_string(),
+        );
+    }
+    code_block.push(format!("({SHARED_CALL_LABEL})"));
+    // push return address
+    code_block.append(&mut vec![
+        at(R_RETURN_ADDRESS),
+        "D = M".to_string(),
+        at(SP),
+        "A = M".to_string(),
+        "M = D".to_string(),
+        at(SP),
+        "M = M + 1".to_string(),
+    ]);
+    // push LCL, ARG, THIS and THAT
+    for pointer in 1..=4 {
+        code_block.append(&mut vec![
+            at(pointer),
+            "D = M".to_string(),
+            at(SP),
+            "A = M".to_string(),
+            "M = D".to_string(),
+            at(SP),
+            "M = M + 1".to_string(),
+        ]);
+    }
+    code_block.append(&mut vec![
+        // ARG = SP - 5 - n_args
+        at(SP),
+        "D = M".to_string(),
+        at(5),
+        "D = D - A".to_string(),
+        at(R_N_ARGS),
+        "D = D - M".to_string(),
+        at(2),
+        "M = D".to_string(),
+        // LCL = SP
+        at(SP),
+        "D = M".to_string(),
+        at(1),
+        "M = D".to_string(),
+        // goto function
+        at(R_FUNCTION),
+        "A = M".to_string(),
+        "0; JMP".to_string(),
+    ]);
+
+    if is_debug_option {
+        code_block.push("\n// shared return routine".to_string());
+    }
+    code_block.push(format!("({SHARED_RETURN_LABEL})"));
+    code_block.append(&mut vec![
+        // R13 = endframe address = LCL
+        at(1),
+        "D = M".to_string(),
+        at(R_RETURN_ADDRESS),
+        "M = D".to_string(),
+        // R14 = return address = *(endframe - 5)
+        at(5),
+        "A = D - A".to_string(),
+        "D = M".to_string(),
+        at(R_FUNCTION),
+        "M = D".to_string(),
+        // *ARG = pop()
+        at(SP),
+        "AM = M - 1".to_string(),
+        "D = M".to_string(),
+        at(2),
+        "A = M".to_string(),
+        "M = D".to_string(),
+        // SP = ARG + 1
+        at(2),
+        "D = M + 1".to_string(),
+        at(SP),
+        "M = D".to_string(),
+    ]);
+    // restore THAT, THIS, ARG and LCL from *(endframe - 1) .. *(endframe - 4)
+    for pointer in (1..=4).rev() {
+        code_block.append(&mut vec![
+            at(R_RETURN_ADDRESS),
+            "AM = M - 1".to_string(),
+            "D = M".to_string(),
+            at(pointer),
+            "M = D".to_string(),
+        ]);
+    }
+    // jump to return address
+    code_block.append(&mut vec![
+        at(R_FUNCTION),
+        "A = M".to_string(),
+        "0; JMP".to_string(),
+    ]);
+    return code_block;
+}
+
+/// Call site using the shared `$$CALL` routine.
+pub fn generate_shared_function_call(
+    function_name: &str,
+    n_args: usize,
+    jump_counter_ref: &mut usize,
+) -> Vec<String> {
+    *jump_counter_ref += 1;
+    let return_label = format!("{}_ret_{}", function_name, *jump_counter_ref);
+
+    return vec![
+        at(&return_label),
+        "D = A".to_string(),
+        at(R_RETURN_ADDRESS),
+        "M = D".to_string(),
+        at(function_name),
+        "D = A".to_string(),
+        at(R_FUNCTION),
+        "M = D".to_string(),
+        at(n_args),
+        "D = A".to_string(),
+        at(R_N_ARGS),
+        "M = D".to_string(),
+        at(SHARED_CALL_LABEL),
+        "0; JMP".to_string(),
+        format!("({return_label})"),
+    ];
+}
+
+/// `return` using the shared `$$RETURN` routine.
+pub fn generate_shared_function_return() -> Vec<String> {
+    return vec![at(SHARED_RETURN_LABEL), "0; JMP".to_string()];
+}
+
+/// Size of the frame saved by a call: return address, LCL, ARG, THIS and THAT.
+const FRAME_SIZE: usize = 5;
+
+/// `call` directly followed by `return` (when `is_tail_calls_option` is set): the called
+/// function reuses the frame of the current one and returns straight to its caller.
+///
+/// The saved frame of the current function is pushed above the new arguments, then both
+/// are moved down to ARG, which leaves the stack as if the caller of the current function
+/// had called `function_name` itself. The copy goes upwards and the destination is never
+/// above the source, so overlapping regions are fine.
+pub fn generate_tail_call(
+    function_name: &str,
+    n_args: usize,
+    jump_counter_ref: &mut usize,
+    is_debug_option: bool,
+) -> Vec<String> {
+    *jump_counter_ref += 1;
+    let copy_label = format!("{}_tail_{}", function_name, *jump_counter_ref);
+
+    let mut code_block: Vec<String> = vec![];
+    if is_debug_option {
+        code_block.push("\n".to_string());
+        code_block.push(format!("// tail call {} {}", function_name, n_args));
+    }
+    // push *(LCL - 5) .. *(LCL - 1), the frame saved when the current function was called
+    for offset in (1..=FRAME_SIZE).rev() {
+        code_block.append(&mut vec![
+            at(1),
+            "D = M".to_string(),
+            at(offset),
+            "A = D - A".to_string(),
+            "D = M".to_string(),
+            at(SP),
+            "A = M".to_string(),
+            "M = D".to_string(),
+            at(SP),
+            "M = M + 1".to_string(),
+        ]);
+    }
+    let block_size = n_args + FRAME_SIZE;
+    code_block.append(&mut vec![
+        // R13 = address of the first new argument
+        at(SP),
+        "D = M".to_string(),
+        at(block_size),
+        "D = D - A".to_string(),
+        at(13),
+        "M = D".to_string(),
+        // R14 = ARG
+        at(2),
+        "D = M".to_string(),
+        at(14),
+        "M = D".to_string(),
+        // R15 = number of words to move (never 0)
+        at(block_size),
+        "D = A".to_string(),
+        at(15),
+        "M = D".to_string(),
+        format!("({copy_label})"),
+        at(13),
+        "A = M".to_string(),
+        "D = M".to_string(),
+        at(14),
+        "A = M".to_string(),
+        "M = D".to_string(), // *R14 = *R13
+        at(13),
+        "M = M + 1".to_string(),
+        at(14),
+        "M = M + 1".to_string(),
+        at(15),
+        "MD = M - 1".to_string(),
+        at(&copy_label),
+        "D; JGT".to_string(),
+        // LCL = SP = ARG + n_args + 5
+        at(2),
+        "D = M".to_string(),
+        at(block_size),
+        "D = D + A".to_string(),
+        at(1),
+        "M = D".to_string(),
+        at(SP),
+        "M = D".to_string(),
+        // goto function
+        at(function_name),
+        "0; JMP".to_string(),
+    ]);
+    return code_block;
+}
@@ -1,88 +1,124 @@
-use std::collections::HashMap;
-
+use crate::parser::Segment;
 use crate::utils::add_padding;
 
-use super::{at, SP};
+use super::{at, ARG, LCL, SP, THAT, THIS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemCmd {
+    Push,
+    Pop,
+}
+
+impl MemCmd {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            MemCmd::Push => "push",
+            MemCmd::Pop => "pop",
+        };
+    }
+}
+
+/// Returns the address of the segment pointer for `local`, `argument`, `this` and `that`.
+fn segment_pointer(mem_segment: Segment) -> Option<usize> {
+    return match mem_segment {
+        Segment::Local => Some(LCL),
+        Segment::Argument => Some(ARG),
+        Segment::This => Some(THIS),
+        Segment::That => Some(THAT),
+        _ => None,
+    };
+}
+
+/// Largest index of `local`, `argument`, `this` and `that` that `push` reaches with an
+/// `A = A + 1` chain; beyond it, adding the index with `D` is shorter.
+const MAX_PUSH_CHAIN_INDEX: usize = 2;
 
-// memory segments:
-const SEGMENT: [&'static str; 9] = [
-    "local", "argument", "constant", "this", "that", "static", "pointer", "temp", "general",
-];
+/// Largest index that `pop` reaches with an `A = A + 1` chain; beyond it, the address is
+/// computed into R15 before popping.
+const MAX_POP_CHAIN_INDEX: usize = 6;
+
+/// Sets A to segmentPointer + index: `A = M` for index 0, then `A = M + 1` followed by
+/// `A = A + 1` for each further index.
+fn chain_address(pointer: usize, mem_index: usize) -> Vec<String> {
+    let mut code_block: Vec<String> = vec![at(pointer)];
+    if mem_index == 0 {
+        code_block.push("A = M".to_string()); // A = base address
+        return code_block;
+    }
+    code_block.push("A = M + 1".to_string()); // A = base address + 1
+    for _ in 1..mem_index {
+        code_block.push("A = A + 1".to_string());
+    }
+    return code_block;
+}
 
 /// Generates assembly for push/pop commands
 ///
+/// Expects arguments already validated by the parser (e.g. no `pop constant`).
+///
 /// # Examples:
 /// To translate:
-/// ```
+/// ```text
 /// push constant 10
 /// ```
 /// Use:
-/// ```
-/// generate_mem_code_block("push", "constant", 10, ..., ..., ...)
+/// ```ignore
+/// generate_mem_code_block(MemCmd::Push, Segment::Constant, 10, ..., ..., ...)
 /// ```
 ///
 pub fn generate_mem_code_block(
-    mem_cmd: &str,
-    mem_segment: &str,
+    mem_cmd: MemCmd,
+    mem_segment: Segment,
     mem_index: usize,
     filename: &str,
     is_debug_option: bool,
     padding: usize,
 ) -> Vec<String> {
-    if !SEGMENT.contains(&mem_segment) {
-        eprintln!(
-            "[ERROR] bad syntax. Memory segment {} does not exist or is not supported.",
-            { mem_segment }
-        );
-        panic!();
-    }
-
-    let label_pointers: HashMap<&str, usize> = HashMap::from([
-        ("SP", 0),
-        ("local", 1),
-        ("argument", 2),
-        ("this", 3),
-        ("that", 4),
-    ]);
-
     let mut code_block: Vec<String> = vec![];
 
     if is_debug_option {
-        let comment = format!("// {} {} {}", mem_cmd, mem_segment, mem_index); // comment indicates which operation is being translated.
+        let comment = format!(
+            "// {} {} {}",
+            mem_cmd.name(),
+            mem_segment.name(),
+            mem_index
+        ); // comment indicates which operation is being translated.
         code_block.push("\n".to_string());
         code_block.push(comment);
     }
 
     match mem_cmd {
-        "push" => {
+        MemCmd::Push => {
             // command: push segment i
             // implementation:
             // addr = segmentPointer +i;
             // *sp = *addr; sp++;
-            match label_pointers.contains_key(mem_segment) {
-                true => {
-                    // TODO: better error handling
-
-                    let mut temp_vec: Vec<String> = vec![
-                        at(*label_pointers.get(mem_segment).unwrap()),
-                        "D = M".to_string(), // D = base address
-                        at(mem_index),
-                        "D = D + A".to_string(), // D  = base address + index
-                        at(15),                  // set A = 15; result: M is now RAM[temp3].
-                        format!("M = D"),        // calc_addr  = D = base address + index
-                        "A = M".to_string(),     // go to the calc_addr
-                        "D = M".to_string(), // store content of the variable that calc_addr points to
-                        at(SP),              // A = 0
-                        "A = M".to_string(), // go to the variable that SP points to
+            match segment_pointer(mem_segment) {
+                Some(pointer) => {
+                    // A = addr, the shortest way for this index
+                    let mut temp_vec: Vec<String> = if mem_index <= MAX_PUSH_CHAIN_INDEX {
+                        chain_address(pointer, mem_index)
+                    } else {
+                        vec![
+                            at(pointer),
+                            "D = M".to_string(), // D = base address
+                            at(mem_index),
+                            "A = D + A".to_string(), // A = base address + index
+                        ]
+                    };
+                    temp_vec.append(&mut vec![
+                        "D = M".to_string(),     // store content of the variable that addr points to
+                        at(SP),                  // A = 0
+                        "A = M".to_string(),     // go to the variable that SP points to
                         "M = D".to_string(), // set the content of the variable to the value previously fetched
                         at(SP),              // A = 0
                         "M = M + 1".to_string(), // increment SP
-                    ];
+                    ]);
                     code_block.append(&mut temp_vec);
                 }
-                false => {
+                None => {
                     match mem_segment {
-                        "constant" => {
+                        Segment::Constant => {
                             // mem_index in this case is actually a constant value
                             // which means that the name `mem_index`
                             // isn't accurate in this case
@@ -99,7 +135,7 @@ pub fn generate_mem_code_block(
 
                             code_block.append(&mut temp_vec);
                         }
-                        "temp" => {
+                        Segment::Temp => {
                             let mut temp_vec: Vec<String> = vec![
                                 at(5 + mem_index),       // A = 5 + i
                                 "D = M".to_string(),     // D = RAM[5+i]
@@ -112,11 +148,7 @@ pub fn generate_mem_code_block(
 
                             code_block.append(&mut temp_vec);
                         }
-                        "pointer" => {
-                            if mem_index != 1 && mem_index != 0 {
-                                eprintln!("[ERROR] bad syntax.");
-                                panic!();
-                            }
+                        Segment::Pointer => {
                             let label: &str = if mem_index == 0 { "THIS" } else { "THAT" };
 
                             let mut temp_vec: Vec<String> = vec![
@@ -131,7 +163,7 @@ pub fn generate_mem_code_block(
 
                             code_block.append(&mut temp_vec);
                         }
-                        "static" => {
+                        Segment::Static => {
                             let mut temp_vec: Vec<String> = vec![
                                 format!("@{}.{}", filename, mem_index), // @Foo.i
                                 "D = M".to_string(),                    // D = content at RAM[Foo.i]
@@ -144,7 +176,7 @@ pub fn generate_mem_code_block(
 
                             code_block.append(&mut temp_vec);
                         }
-                        "general" => {
+                        Segment::General => {
                             // directly access any RAM[n]
                             // be careful when using this
                             let mut temp_vec: Vec<String> = vec![
@@ -158,29 +190,37 @@ pub fn generate_mem_code_block(
                             ];
                             code_block.append(&mut temp_vec);
                         }
-                        _ => {
-                            eprintln!("[ERROR] bad syntax.");
-                            panic!();
+                        Segment::Local | Segment::Argument | Segment::This | Segment::That => {
+                            unreachable!()
                         }
                     }
                 }
             }
         }
-        "pop" => {
+        MemCmd::Pop => {
             // command: pop segment i
             // implementation:
             // addr = segmentPointer +i;
             // sp--; *addr = *sp;
-            match label_pointers.contains_key(mem_segment) {
-                true => {
-                    // TODO: better error handling
+            match segment_pointer(mem_segment) {
+                Some(pointer) if mem_index <= MAX_POP_CHAIN_INDEX => {
                     let mut temp_vec: Vec<String> = vec![
-                        at(*label_pointers.get(mem_segment).unwrap()),
+                        at(SP),
+                        "AM = M - 1".to_string(), // decrement SP, go to the variable that SP points to
+                        "D = M".to_string(),      // store the content in D
+                    ];
+                    temp_vec.append(&mut chain_address(pointer, mem_index)); // A = addr
+                    temp_vec.push("M = D".to_string()); // store the popped value in RAM[addr]
+                    code_block.append(&mut temp_vec);
+                }
+                Some(pointer) => {
+                    let mut temp_vec: Vec<String> = vec![
+                        at(pointer),
                         "D = M".to_string(), // D = base address
                         at(mem_index),
                         "D = D + A".to_string(), // D  = base address + index
                         at(15),                  // set A = temp3; result: M is now RAM[temp3].
-                        format!("M = D"),        // calc_addr  = D = base address + index
+                        "M = D".to_string(),     // calc_addr  = D = base address + index
                         at(SP),                  // A = 0
                         "M = M - 1".to_string(), // decrement SP
                         at(SP),                  // A = 0
@@ -192,13 +232,12 @@ pub fn generate_mem_code_block(
                     ];
                     code_block.append(&mut temp_vec);
                 }
-                false => {
+                None => {
                     match mem_segment {
-                        "constant" => {
-                            eprintln!("[ERROR] bad syntax. Cannot pop a constant.");
-                            panic!();
+                        Segment::Constant => {
+                            unreachable!("cannot pop a constant");
                         }
-                        "temp" => {
+                        Segment::Temp => {
                             let mut temp_vec: Vec<String> = vec![
                                 at(SP),                  // A = 0
                                 "M = M - 1".to_string(), // decrement SP
@@ -212,11 +251,7 @@ pub fn generate_mem_code_block(
                             code_block.append(&mut temp_vec);
                         }
 
-                        "pointer" => {
-                            if mem_index != 1 && mem_index != 0 {
-                                eprintln!("[ERROR] bad syntax.");
-                                panic!();
-                            }
+                        Segment::Pointer => {
                             let label: &str = if mem_index == 0 { "THIS" } else { "THAT" };
 
                             let mut temp_vec: Vec<String> = vec![
@@ -224,7 +259,7 @@ pub fn generate_mem_code_block(
                                 "M = M - 1".to_string(), // decrement SP
                                 at(SP),                  // A = 0
                                 "A = M".to_string(),     // go to the variable that SP points to
-                                "M = D".to_string(),     // store the value in D
+                                "D = M".to_string(),     // store the value in D
                                 at(label), // A = THIS/THAT (this/that are pointers to their respective segments)
                                 "M = D".to_string(), // THIS/THAT = the highest number in the stack (stored in D)
                             ];
@@ -232,7 +267,7 @@ pub fn generate_mem_code_block(
                             code_block.append(&mut temp_vec);
                         }
 
-                        "static" => {
+                        Segment::Static => {
                             let mut temp_vec: Vec<String> = vec![
                                 at(SP),                                 // A = 0
                                 "M = M - 1".to_string(),                // decrement SP
@@ -244,7 +279,7 @@ pub fn generate_mem_code_block(
                             ];
                             code_block.append(&mut temp_vec);
                         }
-                        "general" => {
+                        Segment::General => {
                             let mut temp_vec: Vec<String> = vec![
                                 at(SP),                  // A = 0
                                 "M = M - 1".to_string(), // decrement SP
@@ -257,21 +292,13 @@ pub fn generate_mem_code_block(
 
                             code_block.append(&mut temp_vec);
                         }
-                        _ => {
-                            eprintln!("[ERROR] bad syntax.");
-                            panic!();
+                        Segment::Local | Segment::Argument | Segment::This | Segment::That => {
+                            unreachable!()
                         }
                     }
                 }
             }
         }
-        _ => {
-            eprintln!(
-                "[ERROR] bad syntax. {} cannot be recognized as a memory-access command.",
-                mem_cmd
-            );
-            panic!();
-        }
     }
 
     code_block = if is_debug_option && padding != 0 {
@@ -281,3 +308,87 @@ pub fn generate_mem_code_block(
     };
     return code_block;
 }
+
+/// Address of `temp`, `pointer`, `static` and `general` variables, which need no segment pointer.
+fn fixed_address(mem_segment: Segment, mem_index: usize, filename: &str) -> String {
+    return match mem_segment {
+        Segment::Temp => at(5 + mem_index),
+        Segment::Pointer => at(if mem_index == 0 { "THIS" } else { "THAT" }),
+        Segment::Static => format!("@{}.{}", filename, mem_index),
+        _ => at(mem_index),
+    };
+}
+
+/// Generates assembly for a `push` directly followed by a `pop` (`VmCommand::Move`):
+/// the value is loaded into D and stored without going through the stack.
+pub fn generate_move_code_block(
+    source: Segment,
+    source_index: usize,
+    mem_segment: Segment,
+    mem_index: usize,
+    filename: &str,
+    is_debug_option: bool,
+) -> Vec<String> {
+    let mut code_block: Vec<String> = vec![];
+
+    if is_debug_option {
+        code_block.push("\n".to_string());
+        code_block.push(format!(
+            "// push {} {}, pop {} {}",
+            source.name(),
+            source_index,
+            mem_segment.name(),
+            mem_index
+        ));
+    }
+
+    // addresses too far for an `A = A + 1` chain are computed into R15 before loading D.
+    let computed_target = segment_pointer(mem_segment).filter(|_| mem_index > MAX_POP_CHAIN_INDEX);
+    if let Some(pointer) = computed_target {
+        code_block.append(&mut vec![
+            at(pointer),
+            "D = M".to_string(), // D = base address
+            at(mem_index),
+            "D = D + A".to_string(), // D = base address + index
+            at(15),
+            "M = D".to_string(), // R15 = target address
+        ]);
+    }
+
+    // D = value
+    match (source, segment_pointer(source)) {
+        (Segment::Constant, _) => {
+            code_block.push(at(source_index));
+            code_block.push("D = A".to_string()); // D = constant
+        }
+        (_, Some(pointer)) if source_index <= MAX_PUSH_CHAIN_INDEX => {
+            code_block.append(&mut chain_address(pointer, source_index));
+            code_block.push("D = M".to_string());
+        }
+        (_, Some(pointer)) => {
+            code_block.append(&mut vec![
+                at(pointer),
+                "D = M".to_string(), // D = base address
+                at(source_index),
+                "A = D + A".to_string(), // A = base address + index
+                "D = M".to_string(),
+            ]);
+        }
+        (_, None) => {
+            code_block.push(fixed_address(source, source_index, filename));
+            code_block.push("D = M".to_string());
+        }
+    }
+
+    // RAM[addr] = D
+    match (computed_target, segment_pointer(mem_segment)) {
+        (Some(_), _) => {
+            code_block.push(at(15));
+            code_block.push("A = M".to_string()); // A = target address
+        }
+        (None, Some(pointer)) => code_block.append(&mut chain_address(pointer, mem_index)),
+        (None, None) => code_block.push(fixed_address(mem_segment, mem_index, filename)),
+    }
+    code_block.push("M = D".to_string());
+    return code_block;
+}
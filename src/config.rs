@@ -0,0 +1,130 @@
+use crate::code_generator::{ArithmeticStrategy, BootstrapMode};
+use crate::TranslateOptions;
+
+/// Reads translator options from a config file.
+///
+/// Each line is a `key = value` pair using the same names as the command line
+/// options (without the leading `--`). Empty lines and lines starting with `#`
+/// or `//` are ignored.
+///
+/// # Example:
+/// ```text
+/// # project 7 style test: no bootstrap, custom segments.
+/// bootstrap = none
+/// lcl-base = 300
+/// arg-base = 400
+/// ```
+pub fn apply_config(text: &str, options: &mut TranslateOptions) -> Result<(), String> {
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => {
+                return Err(format!(
+                    "line {}: expected `key = value`, found `{}`.",
+                    line_index + 1,
+                    line
+                ))
+            }
+        };
+        match set_option(options, key, value) {
+            Ok(true) => {}
+            Ok(false) => return Err(format!("line {}: unknown option `{}`.", line_index + 1, key)),
+            Err(message) => return Err(format!("line {}: {}", line_index + 1, message)),
+        }
+    }
+    return Ok(());
+}
+
+/// Sets the option named `key` (e.g. `bootstrap`, `lcl-base`, `global-labels`, `shared-calls`).
+///
+/// Returns `Ok(false)` if `key` is not a known option.
+pub fn set_option(options: &mut TranslateOptions, key: &str, value: &str) -> Result<bool, String> {
+    let parse_address = || -> Result<usize, String> {
+        return match value.parse::<usize>() {
+            Ok(address) if address <= 32767 => Ok(address),
+            _ => Err(format!(
+                "{} expects an address between 0 and 32767, found `{}`.",
+                key, value
+            )),
+        };
+    };
+
+    let parse_bool = || -> Result<bool, String> {
+        return match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(format!("{} expects true or false, found `{}`.", key, value)),
+        };
+    };
+
+    match key {
+        "bootstrap" => {
+            if value == "auto" {
+                options.bootstrap = None;
+            } else {
+                match BootstrapMode::from_name(value) {
+                    Some(mode) => options.bootstrap = Some(mode),
+                    None => {
+                        return Err(format!(
+                            "bootstrap expects one of full, sp-only, none or auto, found `{}`.",
+                            value
+                        ))
+                    }
+                }
+            }
+        }
+        "arithmetic" => match ArithmeticStrategy::from_name(value) {
+            Some(strategy) => options.arithmetic = strategy,
+            None => {
+                return Err(format!(
+                    "arithmetic expects didactic or optimized, found `{}`.",
+                    value
+                ))
+            }
+        },
+        "optimize" => match value {
+            "0" => options.optimization_level = 0,
+            "1" => options.optimization_level = 1,
+            "2" => options.optimization_level = 2,
+            _ => return Err(format!("optimize expects 0, 1 or 2, found `{}`.", value)),
+        },
+        "sp-base" => options.base_addresses.sp = parse_address()?,
+        "lcl-base" => options.base_addresses.lcl = parse_address()?,
+        "arg-base" => options.base_addresses.arg = parse_address()?,
+        "this-base" => options.base_addresses.this = parse_address()?,
+        "that-base" => options.base_addresses.that = parse_address()?,
+        "global-labels" => options.is_global_labels_option = parse_bool()?,
+        "shared-calls" => options.is_shared_calls_option = parse_bool()?,
+        "tail-calls" => options.is_tail_calls_option = parse_bool()?,
+        "shared-comparisons" => options.is_shared_comparisons_option = parse_bool()?,
+        "eliminate-dead-functions" => options.is_eliminate_dead_functions_option = parse_bool()?,
+        "inline" => match value.parse::<usize>() {
+            Ok(max_size) => options.inline_threshold = Some(max_size),
+            _ => {
+                return Err(format!(
+                    "inline expects a number of vm commands, found `{}`.",
+                    value
+                ))
+            }
+        },
+        "keep" => options.kept_functions.extend(
+            value
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty()),
+        ),
+        "allow-undefined" => options.allowed_undefined_functions.extend(
+            value
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty()),
+        ),
+        _ => return Ok(false),
+    }
+    return Ok(true);
+}
@@ -0,0 +1,136 @@
+use crate::parser::Span;
+use colored::*;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in a vm file, rendered rustc-style:
+///
+/// ```text
+/// error: Memory segment foo does not exist or is not supported.
+///  --> Main.vm:3:6
+///   |
+/// 3 | push foo 1
+///   |      ^^^
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Name of the vm file including its extension.
+    pub file: String,
+    /// Position of the offending token. `None` if the problem concerns the whole file.
+    pub span: Option<Span>,
+    /// The source line that contains `span`.
+    pub snippet: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic pointing at `span` inside `source_text`.
+    pub fn new(
+        severity: Severity,
+        file: &str,
+        source_text: &str,
+        span: Span,
+        message: &str,
+    ) -> Diagnostic {
+        let snippet = source_text
+            .lines()
+            .nth(span.line.saturating_sub(1))
+            .unwrap_or_default()
+            .trim_end()
+            .to_string();
+        return Diagnostic {
+            severity,
+            file: file.to_string(),
+            span: Some(span),
+            snippet,
+            message: message.to_string(),
+        };
+    }
+
+    /// Creates a diagnostic that concerns a whole file.
+    pub fn without_span(severity: Severity, file: &str, message: &str) -> Diagnostic {
+        return Diagnostic {
+            severity,
+            file: file.to_string(),
+            span: None,
+            snippet: String::new(),
+            message: message.to_string(),
+        };
+    }
+
+    pub fn error(file: &str, source_text: &str, span: Span, message: &str) -> Diagnostic {
+        return Diagnostic::new(Severity::Error, file, source_text, span, message);
+    }
+
+    pub fn warning(file: &str, source_text: &str, span: Span, message: &str) -> Diagnostic {
+        return Diagnostic::new(Severity::Warning, file, source_text, span, message);
+    }
+
+    pub fn is_error(&self) -> bool {
+        return self.severity == Severity::Error;
+    }
+
+    /// Renders the diagnostic using colors (if the output supports them).
+    pub fn render(&self) -> String {
+        let header = match self.severity {
+            Severity::Error => "error".red().bold(),
+            Severity::Warning => "warning".purple().bold(),
+        };
+        let mut rendered = format!("{}: {}\n", header, self.message.bold());
+
+        let span = match self.span {
+            Some(span) => span,
+            None => {
+                rendered.push_str(&format!(" {} {}\n", "-->".blue().bold(), self.file));
+                return rendered;
+            }
+        };
+
+        let line_number = span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let bar = "|".blue().bold();
+
+        // keep tabs so that the caret lines up with the snippet.
+        let caret_padding: String = self
+            .snippet
+            .chars()
+            .take(span.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let carets = "^".repeat(span.len.max(1));
+        let carets = match self.severity {
+            Severity::Error => carets.red().bold(),
+            Severity::Warning => carets.purple().bold(),
+        };
+
+        rendered.push_str(&format!(
+            "{}{} {}:{}:{}\n",
+            gutter,
+            "-->".blue().bold(),
+            self.file,
+            span.line,
+            span.column
+        ));
+        rendered.push_str(&format!("{} {}\n", gutter, bar));
+        rendered.push_str(&format!(
+            "{} {} {}\n",
+            line_number.blue().bold(),
+            bar,
+            self.snippet
+        ));
+        rendered.push_str(&format!("{} {} {}{}\n", gutter, bar, caret_padding, carets));
+        return rendered;
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.render());
+    }
+}
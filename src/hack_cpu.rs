@@ -0,0 +1,201 @@
+use crate::assembler::{assemble_lines, parse_hack_text, AsmError};
+use std::fmt;
+
+/// Number of words of RAM and ROM.
+pub const RAM_SIZE: usize = 32768;
+pub const ROM_SIZE: usize = 32768;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpuError {
+    /// The program couldn't be assembled or parsed.
+    Load(AsmError),
+    /// The program doesn't fit in ROM.
+    RomOverflow(usize),
+    /// `M` was accessed while `A` pointed outside of RAM.
+    RamOutOfBounds { pc: u16, address: u16 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::Load(e) => write!(f, "{}", e),
+            CpuError::RomOverflow(size) => write!(
+                f,
+                "[ERROR] the program has {} instructions, ROM holds {}.",
+                size, ROM_SIZE
+            ),
+            CpuError::RamOutOfBounds { pc, address } => write!(
+                f,
+                "[ERROR] instruction {} accesses RAM[{}], RAM holds {} words.",
+                pc, address, RAM_SIZE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+/// Headless emulator of the HACK computer: 32K of ROM, 32K of RAM and the A, D and PC registers.
+///
+/// # Example:
+/// ```
+/// use vm_translator::hack_cpu::HackCpu;
+///
+/// let mut cpu = HackCpu::from_asm(&["@2", "D = A", "@3", "D = D + A", "@0", "M = D"]).unwrap();
+/// cpu.run(100).unwrap();
+/// assert_eq!(cpu.ram_value(0), 5);
+/// ```
+pub struct HackCpu {
+    rom: Vec<u16>,
+    pub ram: Vec<u16>,
+    pub a: u16,
+    pub d: u16,
+    pub pc: u16,
+    /// Number of instructions executed since the last reset.
+    pub cycles: u64,
+}
+
+impl HackCpu {
+    pub fn new(rom: Vec<u16>) -> Result<HackCpu, CpuError> {
+        if rom.len() > ROM_SIZE {
+            return Err(CpuError::RomOverflow(rom.len()));
+        }
+        return Ok(HackCpu {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            a: 0,
+            d: 0,
+            pc: 0,
+            cycles: 0,
+        });
+    }
+
+    /// Assembles lines of HACK assembly (e.g. the output of `CodeGenerator`) and loads them.
+    pub fn from_asm<S: AsRef<str>>(lines: &[S]) -> Result<HackCpu, CpuError> {
+        let words = assemble_lines(lines).map_err(CpuError::Load)?;
+        return HackCpu::new(words);
+    }
+
+    /// Loads a program in the `.hack` text format.
+    pub fn from_hack_text(text: &str) -> Result<HackCpu, CpuError> {
+        let words = parse_hack_text(text).map_err(CpuError::Load)?;
+        return HackCpu::new(words);
+    }
+
+    pub fn rom(&self) -> &[u16] {
+        return &self.rom;
+    }
+
+    /// Returns RAM[address] as a signed value, as displayed by the CPU emulator.
+    pub fn ram_value(&self, address: usize) -> i16 {
+        return self.ram[address] as i16;
+    }
+
+    pub fn set_ram_value(&mut self, address: usize, value: i16) {
+        self.ram[address] = value as u16;
+    }
+
+    /// Sets the registers back to 0 (the reset signal). RAM is left untouched.
+    pub fn reset(&mut self) {
+        self.a = 0;
+        self.d = 0;
+        self.pc = 0;
+        self.cycles = 0;
+    }
+
+    /// True once the program counter has left the program.
+    pub fn is_halted(&self) -> bool {
+        return usize::from(self.pc) >= self.rom.len();
+    }
+
+    /// Executes a single instruction. Does nothing if the cpu is halted.
+    pub fn step(&mut self) -> Result<(), CpuError> {
+        if self.is_halted() {
+            return Ok(());
+        }
+        let instruction = self.rom[usize::from(self.pc)];
+        self.cycles += 1;
+
+        // A-instruction: 0vvv vvvv vvvv vvvv
+        if instruction & 0x8000 == 0 {
+            self.a = instruction;
+            self.pc += 1;
+            return Ok(());
+        }
+
+        // C-instruction: 111a cccc ccdd djjj
+        let a_bit = instruction >> 12 & 1;
+        let comp_bits = instruction >> 6 & 0b111111;
+        let dest_bits = instruction >> 3 & 0b111;
+        let jump_bits = instruction & 0b111;
+
+        let is_m_read = a_bit == 1;
+        let is_m_written = dest_bits & 0b001 != 0;
+        if (is_m_read || is_m_written) && usize::from(self.a) >= RAM_SIZE {
+            return Err(CpuError::RamOutOfBounds {
+                pc: self.pc,
+                address: self.a,
+            });
+        }
+
+        let y = if is_m_read {
+            self.ram[usize::from(self.a)]
+        } else {
+            self.a
+        };
+        let out = alu(self.d, y, comp_bits);
+
+        let old_a = self.a;
+        if is_m_written {
+            self.ram[usize::from(old_a)] = out;
+        }
+        if dest_bits & 0b100 != 0 {
+            self.a = out;
+        }
+        if dest_bits & 0b010 != 0 {
+            self.d = out;
+        }
+
+        let signed_out = out as i16;
+        let is_jump = (jump_bits & 0b100 != 0 && signed_out < 0)
+            || (jump_bits & 0b010 != 0 && signed_out == 0)
+            || (jump_bits & 0b001 != 0 && signed_out > 0);
+        self.pc = if is_jump { old_a } else { self.pc + 1 };
+        return Ok(());
+    }
+
+    /// Executes up to `max_cycles` instructions, stopping early if the cpu halts.
+    ///
+    /// Returns the number of instructions executed.
+    pub fn run(&mut self, max_cycles: u64) -> Result<u64, CpuError> {
+        let start = self.cycles;
+        while self.cycles - start < max_cycles && !self.is_halted() {
+            self.step()?;
+        }
+        return Ok(self.cycles - start);
+    }
+}
+
+/// The HACK ALU. `comp_bits` are the `zx nx zy ny f no` control bits.
+fn alu(x: u16, y: u16, comp_bits: u16) -> u16 {
+    let bit = |n: u16| comp_bits >> (5 - n) & 1 == 1;
+    let mut x = x;
+    let mut y = y;
+    if bit(0) {
+        x = 0;
+    }
+    if bit(1) {
+        x = !x;
+    }
+    if bit(2) {
+        y = 0;
+    }
+    if bit(3) {
+        y = !y;
+    }
+    let mut out = if bit(4) { x.wrapping_add(y) } else { x & y };
+    if bit(5) {
+        out = !out;
+    }
+    return out;
+}
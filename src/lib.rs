@@ -0,0 +1,424 @@
+#![allow(clippy::needless_return)]
+//! Translates HACK virtual machine code to HACK assembly.
+//!
+//! The translator works on vm sources held in memory, which lets other tools
+//! (test harnesses, editors, build scripts) embed it instead of shelling out:
+//!
+//! ```
+//! use vm_translator::{translate, TranslateOptions, VmSource};
+//!
+//! let sources = vec![VmSource::new("Main", "push constant 7\npush constant 8\nadd\n")];
+//! let program = translate(&sources, &TranslateOptions::default()).unwrap();
+//! assert!(program.lines.contains(&"@7".to_string()));
+//! ```
+pub mod analysis;
+pub mod assembler;
+pub mod budget;
+pub mod code_generator;
+pub mod config;
+pub mod diagnostic;
+pub mod hack_cpu;
+pub mod optimizer;
+pub mod parser;
+pub mod stats;
+pub mod test_script;
+pub mod utils;
+pub mod verify;
+pub mod vm_interpreter;
+pub mod vm_optimizer;
+
+use analysis::{check_calls, check_labels};
+use assembler::parse_asm;
+use budget::SizeReport;
+use code_generator::{
+    ArithmeticStrategy, BaseAddresses, BootstrapMode, CodeGenerator, SharedComparisonsReport,
+};
+use diagnostic::{Diagnostic, Severity};
+use optimizer::{optimize, OptimizerReport};
+use parser::{parse, ParseError, VmFile};
+use stats::CostProfile;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+use vm_optimizer::{
+    eliminate_dead_functions, inline_functions, optimize_files, DeadFunctionReport,
+};
+
+pub enum PathType {
+    Dir,
+    File,
+}
+
+/// A single vm file held in memory.
+///
+/// `name` is the filename without the `.vm` extension; it is used to name
+/// the static variables of the file (`Foo.i`).
+#[derive(Debug, Clone)]
+pub struct VmSource {
+    pub name: String,
+    pub text: String,
+}
+
+impl VmSource {
+    pub fn new(name: &str, text: &str) -> VmSource {
+        return VmSource {
+            name: name.to_string(),
+            text: text.to_string(),
+        };
+    }
+
+    /// Reads a vm file from disk.
+    pub fn from_path(path: &Path) -> io::Result<VmSource> {
+        let file = File::open(path)?;
+        let mut buf_reader = BufReader::new(file);
+        let mut text = String::new();
+        buf_reader.read_to_string(&mut text)?;
+
+        let name = path // get filename and remove extension.
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .trim_end_matches(".vm")
+            .to_string();
+
+        return Ok(VmSource { name, text });
+    }
+}
+
+/// Assembly produced by [`translate`], one instruction or label per line.
+#[derive(Debug, Clone, Default)]
+pub struct AsmProgram {
+    pub lines: Vec<String>,
+    /// Problems that didn't prevent the translation.
+    pub warnings: Vec<Diagnostic>,
+    /// Set when the optimizers ran (`TranslateOptions::optimization_level` >= 1).
+    pub optimizer_report: Option<OptimizerReport>,
+    /// Set when `TranslateOptions::is_eliminate_dead_functions_option` is.
+    pub dead_function_report: Option<DeadFunctionReport>,
+    /// Set when `TranslateOptions::is_shared_comparisons_option` is.
+    pub shared_comparisons_report: Option<SharedComparisonsReport>,
+    /// Set when `TranslateOptions::is_size_report_option` is.
+    pub size_report: Option<SizeReport>,
+    /// Set when `TranslateOptions::is_stats_option` is.
+    pub cost_profile: Option<CostProfile>,
+}
+
+impl fmt::Display for AsmProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        return Ok(());
+    }
+}
+
+/// Every problem found while translating, returned when at least one of them is an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslateError {
+    /// Errors and warnings in the order they were found.
+    pub diagnostics: Vec<Diagnostic>,
+    /// True if translation stopped early because `TranslateOptions::error_limit` was reached.
+    pub is_error_limit_reached: bool,
+}
+
+impl TranslateError {
+    pub fn error_count(&self) -> usize {
+        return self.diagnostics.iter().filter(|d| d.is_error()).count();
+    }
+}
+
+impl fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diagnostic in &self.diagnostics {
+            writeln!(f, "{}", diagnostic)?;
+        }
+        let error_count = self.error_count();
+        write!(
+            f,
+            "[ERROR] aborting due to {} error{}",
+            error_count,
+            if error_count == 1 { "" } else { "s" }
+        )?;
+        if self.is_error_limit_reached {
+            write!(
+                f,
+                " (error limit reached, further errors were not reported)"
+            )?;
+        }
+        return writeln!(f, ".");
+    }
+}
+
+impl std::error::Error for TranslateError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct TranslateOptions {
+    /// Annotates the output with the vm commands being translated.
+    pub is_debug_option: bool,
+    /// Stops reporting errors once this many have been found. `None` reports every error.
+    pub error_limit: Option<usize>,
+    /// `None` emits the full bootstrap if `Sys.init` is defined and no bootstrap otherwise.
+    pub bootstrap: Option<BootstrapMode>,
+    /// Values given to the segment pointers by the full bootstrap.
+    pub base_addresses: BaseAddresses,
+    /// Keeps vm labels verbatim instead of scoping them to their function (`Function$label`).
+    pub is_global_labels_option: bool,
+    /// Functions that may be called without being defined (`Class.function` or `Class.*`),
+    /// in addition to the Jack OS classes.
+    pub allowed_undefined_functions: Vec<String>,
+    /// Emits shared `$$CALL` and `$$RETURN` routines once instead of inlining the calling
+    /// convention at every call site and `return`.
+    pub is_shared_calls_option: bool,
+    /// Emits shared `$$EQ`, `$$GT` and `$$LT` routines once instead of inlining every comparison.
+    pub is_shared_comparisons_option: bool,
+    /// Translates a `call` directly followed by `return` inside a function as a jump that
+    /// reuses the frame of the current function.
+    pub is_tail_calls_option: bool,
+    /// Code sequences used for arithmetic and logical commands.
+    pub arithmetic: ArithmeticStrategy,
+    /// 0 translates command by command, 1 runs the peephole optimizer over the output, 2 also
+    /// optimizes the vm commands before translating them.
+    pub optimization_level: u8,
+    /// Omits the functions that can't be reached from the entry points of the program.
+    pub is_eliminate_dead_functions_option: bool,
+    /// Functions kept by dead function elimination even if unreachable (`Class.function`
+    /// or `Class.*`).
+    pub kept_functions: Vec<String>,
+    /// Replaces calls to leaf functions of at most this many vm commands with their body.
+    /// `None` inlines nothing.
+    pub inline_threshold: Option<usize>,
+    /// Counts the instructions of every function and file, and the static variables of
+    /// every file (`AsmProgram::size_report`).
+    pub is_size_report_option: bool,
+    /// Attributes the instructions to the vm commands and functions they were generated
+    /// for (`AsmProgram::cost_profile`).
+    pub is_stats_option: bool,
+}
+
+/// Translates the given vm sources to a single assembly program.
+///
+/// The sources are translated in the order they are supplied.
+pub fn translate(
+    sources: &[VmSource],
+    options: &TranslateOptions,
+) -> Result<AsmProgram, TranslateError> {
+    let program = parse_program(sources, options)?;
+
+    let mut files = program.files;
+    if let Some(max_size) = options.inline_threshold {
+        files = inline_functions(&files, max_size);
+    }
+    let mut dead_function_report: Option<DeadFunctionReport> = None;
+    if options.is_eliminate_dead_functions_option {
+        let (live_files, report) = eliminate_dead_functions(&files, options);
+        files = live_files;
+        dead_function_report = Some(report);
+    }
+
+    let unoptimized_files = files.clone();
+    if options.optimization_level >= 2 {
+        files = optimize_files(&files);
+    }
+
+    let warnings = program.warnings;
+    let into_error = |diagnostics: Vec<Diagnostic>| TranslateError {
+        diagnostics: [warnings.clone(), diagnostics].concat(),
+        is_error_limit_reached: false,
+    };
+    let (code_blocks, lines, optimizer_report) =
+        generate_lines(&files, &unoptimized_files, options).map_err(|e| into_error(vec![e]))?;
+    let mut shared_comparisons_report: Option<SharedComparisonsReport> = None;
+    if options.is_shared_comparisons_option {
+        let inline_options = TranslateOptions {
+            is_shared_comparisons_option: false,
+            ..options.clone()
+        };
+        let (_, inline_lines, _) = generate_lines(&files, &unoptimized_files, &inline_options)
+            .map_err(|e| into_error(vec![e]))?;
+        shared_comparisons_report = Some(SharedComparisonsReport {
+            inline_instructions: budget::program_size(&files, &inline_lines)
+                .map_err(|e| into_error(vec![e]))?,
+            shared_instructions: budget::program_size(&files, &lines)
+                .map_err(|e| into_error(vec![e]))?,
+        });
+    }
+    // the full size report also lists the biggest contributors when a limit is exceeded.
+    let is_over_limits = budget::exceeds_limits(&files, &lines).map_err(|e| into_error(vec![e]))?;
+    let mut size_report: Option<SizeReport> = None;
+    if options.is_size_report_option || is_over_limits {
+        let is_optimized = options.optimization_level >= 1;
+        let report = SizeReport::new(&files, &code_blocks, &lines, is_optimized)
+            .map_err(|e| into_error(vec![e]))?;
+        let errors = report.errors();
+        if !errors.is_empty() {
+            return Err(into_error(errors));
+        }
+        size_report = Some(report);
+    }
+    let mut cost_profile: Option<CostProfile> = None;
+    if options.is_stats_option {
+        cost_profile =
+            Some(CostProfile::new(&files, &code_blocks).map_err(|e| into_error(vec![e]))?);
+    }
+    return Ok(AsmProgram {
+        lines,
+        warnings,
+        optimizer_report,
+        dead_function_report,
+        shared_comparisons_report,
+        size_report,
+        cost_profile,
+    });
+}
+
+/// Code blocks, final lines and optimizer report, see `generate_lines`.
+type GeneratedLines = (Vec<Vec<String>>, Vec<String>, Option<OptimizerReport>);
+
+/// Translates `files` and, at -O1, runs the peephole optimizer over the result.
+///
+/// Returns the code blocks before peephole optimization (see
+/// `CodeGenerator::generate_code_blocks`), the final lines and the optimizer report.
+/// `unoptimized_files` are `files` before the vm optimizer, for the report.
+///
+/// Fails if the generated assembly doesn't parse, since it can't be optimized then.
+fn generate_lines(
+    files: &[VmFile],
+    unoptimized_files: &[VmFile],
+    options: &TranslateOptions,
+) -> Result<GeneratedLines, Diagnostic> {
+    let code_blocks = CodeGenerator::new(options).generate_code_blocks(files);
+    let mut lines = code_blocks.concat();
+
+    let mut optimizer_report: Option<OptimizerReport> = None;
+    if options.optimization_level >= 1 {
+        let mut unoptimized_lines = lines.clone();
+        if options.optimization_level >= 2 {
+            unoptimized_lines = CodeGenerator::new(options).generate_code(unoptimized_files);
+        }
+        let invalid_assembly =
+            |e| budget::invalid_assembly(&budget::first_file_name(files), "the program", e);
+        let before = parse_asm(&unoptimized_lines).map_err(invalid_assembly)?;
+        let instructions = parse_asm(&lines).map_err(invalid_assembly)?;
+        let optimized = optimize(&instructions);
+        optimizer_report = Some(optimizer::report(&before, &optimized));
+        // the optimized output has no comments, even with `is_debug_option`.
+        lines = optimized.iter().map(|i| i.to_string()).collect();
+    }
+    return Ok((code_blocks, lines, optimizer_report));
+}
+
+/// Parsed and checked vm files, ready to be translated or interpreted.
+#[derive(Debug, Clone)]
+pub struct VmProgram {
+    pub files: Vec<VmFile>,
+    /// Problems that didn't prevent the translation.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Parses the given vm sources and runs the semantic checks (labels, calls) over them.
+pub fn parse_program(
+    sources: &[VmSource],
+    options: &TranslateOptions,
+) -> Result<VmProgram, TranslateError> {
+    let mut diagnostics = DiagnosticList::new(options.error_limit);
+
+    let mut files: Vec<VmFile> = vec![];
+    let mut file_sources: Vec<&VmSource> = vec![]; // file_sources[i] was parsed into files[i].
+    for source in sources {
+        match parse(source) {
+            Ok(file) => {
+                files.push(file);
+                file_sources.push(source);
+            }
+            Err(errors) => {
+                for ParseError { span, message } in errors {
+                    diagnostics.push(Diagnostic::error(
+                        &format!("{}.vm", source.name),
+                        &source.text,
+                        span,
+                        &message,
+                    ));
+                }
+            }
+        }
+    }
+
+    diagnostics.extend(check_labels(
+        &file_sources,
+        &files,
+        options.is_global_labels_option,
+    ));
+    diagnostics.extend(check_calls(
+        &file_sources,
+        &files,
+        &options.allowed_undefined_functions,
+    ));
+
+    // a program without `Sys.init` is only expected when there is no bootstrap calling it.
+    if options
+        .bootstrap
+        .unwrap_or_else(|| BootstrapMode::detect(&files))
+        == BootstrapMode::Full
+    {
+        for expected in ["Sys", "Main"] {
+            if !sources.iter().any(|s| s.name == expected) {
+                diagnostics.push(Diagnostic::without_span(
+                    Severity::Warning,
+                    &format!("{}.vm", expected),
+                    &format!("file {}.vm does not exist in directory.", expected),
+                ));
+            }
+        }
+    }
+
+    if diagnostics.error_count > 0 {
+        return Err(TranslateError {
+            diagnostics: diagnostics.list,
+            is_error_limit_reached: diagnostics.is_error_limit_reached,
+        });
+    }
+
+    return Ok(VmProgram {
+        files,
+        warnings: diagnostics.list,
+    });
+}
+
+/// Collects the diagnostics of a run, dropping errors past the error limit.
+struct DiagnosticList {
+    list: Vec<Diagnostic>,
+    error_count: usize,
+    error_limit: usize,
+    is_error_limit_reached: bool,
+}
+
+impl DiagnosticList {
+    fn new(error_limit: Option<usize>) -> DiagnosticList {
+        return DiagnosticList {
+            list: vec![],
+            error_count: 0,
+            error_limit: error_limit.unwrap_or(usize::MAX),
+            is_error_limit_reached: false,
+        };
+    }
+
+    fn push(&mut self, diagnostic: Diagnostic) {
+        if diagnostic.is_error() {
+            if self.error_count == self.error_limit {
+                self.is_error_limit_reached = true;
+                return;
+            }
+            self.error_count += 1;
+        }
+        self.list.push(diagnostic);
+    }
+
+    fn extend(&mut self, diagnostics: Vec<Diagnostic>) {
+        for diagnostic in diagnostics {
+            self.push(diagnostic);
+        }
+    }
+}
@@ -1,12 +1,101 @@
-mod code_generator;
-mod utils;
-use code_generator::*;
-use core::panic;
-use std::{env, path::Path};
+#![allow(clippy::needless_return)]
+use std::{
+    env, fs,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+};
+use vm_translator::{
+    assembler::{assemble_lines, to_hack_text},
+    config::{apply_config, set_option},
+    hack_cpu::{HackCpu, RAM_SIZE},
+    parse_program,
+    parser::VmFile,
+    stats::StatsFormat,
+    test_script::{compare_output, parse_script, run_script},
+    translate,
+    utils::{create_output_path, search_vm_files, write_to_file},
+    verify::Verifier,
+    vm_interpreter::VmInterpreter,
+    vm_optimizer::{eliminate_dead_functions, inline_functions, optimize_files},
+    AsmProgram, PathType, TranslateOptions, VmProgram, VmSource,
+};
 
-pub enum PathType {
-    Dir,
-    File,
+const USAGE: &str = "usage:
+    vm_translator --dir directory_path   => output: directory_path/directory.asm
+    vm_translator --file file_path       => output: file.asm
+    vm_translator run {--dir x or --file x.vm|x.asm|x.hack} [--cycles n] [--ram cells] [--set cell=value,...]
+                                         => runs the program on the HACK cpu emulator, then prints RAM cells
+    vm_translator interpret {--dir x or --file x.vm} [--steps n] [--ram cells] [--set cell=value,...]
+                                         => runs the vm program on the reference interpreter, then prints RAM cells
+    vm_translator verify {--dir x or --file x.vm} [--steps n] [--set cell=value,...]
+                                         => runs the vm program on the interpreter and its translation on the
+                                            cpu emulator, and reports the first vm command where they diverge
+    vm_translator test --script x.tst    => runs a CPU emulator test script against the translation of the
+                                            vm files of its directory, writes the .out file and compares
+                                            it with the .cmp file
+
+options:
+    --debug                         annotate the output with the vm commands (output: *.debug.asm)
+    --error-limit n                 stop after reporting n errors
+    --bootstrap=full|sp-only|none   bootstrap code to emit (default: full if Sys.init is defined, none otherwise)
+    --sp-base, --lcl-base, --arg-base, --this-base, --that-base n
+                                    initial value of a segment pointer set by the bootstrap
+    --global-labels                 keep vm labels verbatim instead of scoping them as Function$label
+    --shared-calls                  emit the calling convention once, as $$CALL and $$RETURN routines
+                                    shared by every call site (smaller ROM, slower calls)
+    --tail-calls                    translate a call directly followed by return as a jump reusing the
+                                    frame of the current function (not supported by verify)
+    --arithmetic=didactic|optimized code for arithmetic and logical commands: through R13/R14
+                                    (default) or in place on the stack
+    --shared-comparisons            emit eq, gt and lt once, as $$EQ, $$GT and $$LT routines, and report
+                                    the ROM words saved
+    -O0, -O1, -O2                   optimization level: -O1 runs the peephole optimizer over the output
+                                    (the debug comments are dropped), -O2 also folds constants and
+                                    fuses push/pop pairs in the vm code
+    --inline n                      replace calls to leaf functions of at most n vm commands (without
+                                    labels) with their body
+    --eliminate-dead-functions      omit the functions unreachable from Sys.init (or the calls made before
+                                    the first function of a file) and report them
+    --keep names                    comma separated functions (Class.function or Class.*) kept by
+                                    --eliminate-dead-functions even if unreachable
+    --allow-undefined names         comma separated functions (Class.function or Class.*) that may be
+                                    called without being defined, in addition to the Jack OS
+    --config file                   read options from a file of `option = value` lines
+    --size-report                   print the instructions of every function and file, and the static
+                                    variables of every file
+    --stats[=table|json|csv]        print the instructions generated for every vm function, by command
+                                    kind, instead of writing the output file
+    --emit asm|hack                 output assembly (default) or machine code (output: *.hack)
+
+run, interpret and verify options:
+    --cycles n                      number of instructions to execute (default: 1000000)
+    --steps n                       number of vm commands to interpret (default: 1000000)
+    --ram cells                     RAM cells to print, e.g. 0,256-260 (default: 0-15)
+    --set cell=value,...            RAM values set before running, e.g. 0=256,1=300";
+
+/// Options of the form `--name value` or `--name=value` that are forwarded to `set_option`.
+const TRANSLATE_OPTIONS: [&str; 10] = [
+    "bootstrap",
+    "arithmetic",
+    "sp-base",
+    "lcl-base",
+    "arg-base",
+    "this-base",
+    "that-base",
+    "allow-undefined",
+    "keep",
+    "inline",
+];
+
+const DEFAULT_CYCLES: u64 = 1_000_000;
+
+/// Prints `message` followed by the usage, then exits with a non-zero code.
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("[ERROR] {}", message);
+    eprintln!("{}", USAGE);
+    process::exit(2);
 }
 
 fn main() {
@@ -14,55 +103,587 @@ fn main() {
     // 1) ./program --dir directory_path => output directory.asm
     // 2) ./program --file file_path => output: file.asm
     // 3) ./program {--dir x or --file x} --debug or ./program --debug {--dir x or --file x}
+    // 4) ./program run {--dir x or --file x} --cycles n --ram 0,256-260
+    // 5) ./program interpret {--dir x or --file x} --steps n --ram 0,256-260
+    // 6) ./program verify {--dir x or --file x} --steps n
+    // 7) ./program test --script x.tst
 
     let args: Vec<String> = env::args().collect();
 
     if args.len() < 3 {
-        eprintln!("[ERROR] incorrect arguments."); // TODO: print help!
-        panic!();
+        exit_with_usage("incorrect arguments.");
+    }
+
+    match args[1].as_str() {
+        "run" => run_mode(&args),
+        "interpret" => interpret_mode(&args),
+        "verify" => verify_mode(&args),
+        "test" => test_mode(&args),
+        _ => translate_mode(&args),
+    }
+}
+
+/// Translates a vm file or directory and writes the output file.
+fn translate_mode(args: &[String]) {
+    let (path_str, path_type) = path_option(args);
+    let path = Path::new(&path_str);
+
+    let emit = find_option(args, "--emit").unwrap_or_else(|| "asm".to_string());
+    if emit != "asm" && emit != "hack" {
+        exit_with_usage("--emit expects asm or hack.");
+    }
+
+    let stats_format = stats_option(args);
+    let mut options = translate_options(args);
+    options.is_size_report_option = args.contains(&"--size-report".to_string());
+    options.is_stats_option = stats_format.is_some();
+    let mut program = translate_or_exit(path, &path_type, &options);
+    if let (Some(format), Some(profile)) = (stats_format, &program.cost_profile) {
+        print!("{}", profile.render(format));
+        return;
+    }
+    if let Some(report) = &program.shared_comparisons_report {
+        println!(
+            "Shared comparisons: {} ROM words instead of {} ({} saved).",
+            report.shared_instructions,
+            report.inline_instructions,
+            report.saved_instructions()
+        );
+    }
+    if let Some(report) = &program.size_report {
+        print!("{}", report);
+    }
+    if let Some(report) = &program.dead_function_report {
+        let dropped_commands: usize = report.dropped.iter().map(|(_, len)| len).sum();
+        println!(
+            "Dead functions: {} of {} functions omitted ({} vm commands).",
+            report.dropped.len(),
+            report.function_count,
+            dropped_commands
+        );
+        for (name, len) in &report.dropped {
+            println!("    {} ({} vm commands)", name, len);
+        }
+    }
+    if let Some(report) = program.optimizer_report {
+        println!(
+            "Optimizer: {} instructions before, {} after.",
+            report.instructions_before, report.instructions_after
+        );
+    }
+
+    let mut output_path = create_output_path(path, &path_type, options.is_debug_option);
+    let mut hack_text = String::new();
+    if emit == "hack" {
+        hack_text = match assemble_lines(&program.lines) {
+            Ok(words) => to_hack_text(&words),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        };
+        output_path = format!("{}.hack", output_path.trim_end_matches(".asm"));
+    }
+    println!("Output: {output_path}");
+
+    let mut output_file = match File::create(&output_path) {
+        Ok(file) => file, // create asm file.
+        Err(why) => {
+            eprintln!(
+                "[ERROR] couldn't create output file using the following path: {}: {}",
+                output_path, why
+            );
+            process::exit(1);
+        }
+    };
+    if emit == "hack" {
+        if let Err(why) = output_file.write_all(hack_text.as_bytes()) {
+            eprintln!("[ERROR] couldn't write {}: {}", output_path, why);
+            process::exit(1);
+        }
+    } else {
+        write_to_file(&mut output_file, &mut program.lines);
+    }
+}
+
+/// `--stats` (table) or `--stats=table|json|csv`.
+fn stats_option(args: &[String]) -> Option<StatsFormat> {
+    if args.contains(&"--stats".to_string()) {
+        return Some(StatsFormat::Table);
+    }
+    let value = args.iter().find_map(|arg| arg.strip_prefix("--stats="))?;
+    return match StatsFormat::from_name(value) {
+        Some(format) => Some(format),
+        None => exit_with_usage("--stats expects table, json or csv."),
+    };
+}
+
+/// Runs a program on the HACK cpu emulator and prints the selected RAM cells.
+///
+/// vm files are translated first; `.asm` and `.hack` files are loaded as they are.
+fn run_mode(args: &[String]) {
+    let (path_str, path_type) = path_option(args);
+    let path = Path::new(&path_str);
+
+    let cycles: u64 = match find_option(args, "--cycles") {
+        Some(value) => match value.parse() {
+            Ok(n) => n,
+            Err(_) => exit_with_usage("--cycles expects a positive integer."),
+        },
+        None => DEFAULT_CYCLES,
+    };
+    let ram_cells = ram_cells_option(args);
+
+    let loaded = if path_str.ends_with(".asm") {
+        fs::read_to_string(path).map(|text| HackCpu::from_asm(&text.lines().collect::<Vec<&str>>()))
+    } else if path_str.ends_with(".hack") {
+        fs::read_to_string(path).map(|text| HackCpu::from_hack_text(&text))
+    } else {
+        let program = translate_or_exit(path, &path_type, &translate_options(args));
+        Ok(HackCpu::from_asm(&program.lines))
+    };
+    let mut cpu = match loaded {
+        Ok(Ok(cpu)) => cpu,
+        Ok(Err(e)) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        Err(why) => {
+            eprintln!("couldn't open {}: {}", path_str, why);
+            process::exit(1);
+        }
+    };
+
+    for (cell, value) in ram_assignments(args) {
+        cpu.set_ram_value(cell, value);
+    }
+
+    let executed = match cpu.run(cycles) {
+        Ok(executed) => executed,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let state = if cpu.is_halted() {
+        "halted"
+    } else {
+        "still running"
+    };
+    println!("Executed {} instructions ({}).", executed, state);
+    for cell in ram_cells {
+        println!("RAM[{}] = {}", cell, cpu.ram_value(cell));
     }
+}
 
-    let mut is_debug_option = false;
-    if args.contains(&"--debug".to_string()) {
-        is_debug_option = true;
+/// Runs vm files on the reference vm interpreter and prints the selected RAM cells.
+fn interpret_mode(args: &[String]) {
+    let (path_str, path_type) = path_option(args);
+    let path = Path::new(&path_str);
+
+    let steps = steps_option(args);
+    let ram_cells = ram_cells_option(args);
+
+    let options = translate_options(args);
+    let program = parse_or_exit(path, &path_type, &options);
+
+    let files = optimized_files(program, &options);
+    let mut interpreter = VmInterpreter::new(&files, &options);
+    for (cell, value) in ram_assignments(args) {
+        interpreter.set_ram_value(cell, value);
     }
 
-    let is_dir_option = args.contains(&"--dir".to_string());
-    let is_file_option = args.contains(&"--file".to_string());
+    let executed = match interpreter.run(steps) {
+        Ok(executed) => executed,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let state = if interpreter.is_halted() {
+        "halted"
+    } else {
+        "still running"
+    };
+    println!("Executed {} vm commands ({}).", executed, state);
+    for cell in ram_cells {
+        println!("RAM[{}] = {}", cell, interpreter.ram_value(cell));
+    }
+}
+
+/// Runs vm files on the reference interpreter and their translation on the HACK cpu
+/// emulator side by side, and reports the first vm command at which they diverge.
+fn verify_mode(args: &[String]) {
+    let (path_str, path_type) = path_option(args);
+    let path = Path::new(&path_str);
+
+    let steps = steps_option(args);
+    let options = translate_options(args);
+    let program = parse_or_exit(path, &path_type, &options);
+
+    // the interpreter keeps the frame of every call, unlike the translated tail calls.
+    if options.is_tail_calls_option {
+        exit_with_usage("verify doesn't support --tail-calls.");
+    }
+    let files = optimized_files(program, &options);
+    let mut verifier = match Verifier::new(&files, &options) {
+        Ok(verifier) => verifier,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    for (cell, value) in ram_assignments(args) {
+        verifier.set_ram_value(cell, value);
+    }
+
+    let report = match verifier.run(steps) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    if let Some(divergence) = report.divergence {
+        eprint!("{}", divergence);
+        process::exit(1);
+    }
+
+    let state = if report.is_halted {
+        "halted"
+    } else {
+        "still running"
+    };
+    println!(
+        "No divergence after {} vm commands ({}).",
+        report.steps, state
+    );
+}
+
+/// Runs a CPU emulator test script (`.tst`) against the translation of the vm files of
+/// its directory, writes the `.out` file and compares it with the `.cmp` file.
+fn test_mode(args: &[String]) {
+    let script_path = match find_option(args, "--script") {
+        Some(script_path) => PathBuf::from(script_path),
+        None => exit_with_usage("test expects --script file.tst."),
+    };
+    let script_text = match fs::read_to_string(&script_path) {
+        Ok(text) => text,
+        Err(why) => exit_with_usage(&format!("couldn't open {}: {}", script_path.display(), why)),
+    };
+    let commands = match parse_script(&script_text) {
+        Ok(commands) => commands,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let dir = match script_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let options = translate_options(args);
+    let mut loader = |name: Option<&str>| -> Result<HackCpu, String> {
+        return match name {
+            Some(name) if name.ends_with(".hack") => fs::read_to_string(dir.join(name))
+                .map_err(|why| format!("couldn't open {}: {}", name, why))
+                .and_then(|text| HackCpu::from_hack_text(&text).map_err(|e| e.to_string())),
+            Some(name) if !name.ends_with(".asm") => Err(format!(
+                "cannot load {}, only .asm and .hack programs can be tested.",
+                name
+            )),
+            // the program is the translation of the vm files next to the script.
+            _ => {
+                let program = translate_or_exit(&dir, &PathType::Dir, &options);
+                HackCpu::from_asm(&program.lines).map_err(|e| e.to_string())
+            }
+        };
+    };
+    let run = match run_script(&commands, &mut loader) {
+        Ok(run) => run,
+        Err(message) => {
+            eprintln!("[ERROR] {}", message);
+            process::exit(1);
+        }
+    };
+    for echo in &run.echoes {
+        println!("{}", echo);
+    }
+
+    if let Some(output_file) = &run.output_file {
+        let output_path = dir.join(output_file);
+        let mut output_text = run.output.join("\n");
+        output_text.push('\n');
+        if let Err(why) = fs::write(&output_path, output_text) {
+            eprintln!("[ERROR] couldn't write {}: {}", output_path.display(), why);
+            process::exit(1);
+        }
+        println!("Output: {}", output_path.display());
+    }
+
+    let compare_file = match &run.compare_file {
+        Some(compare_file) => dir.join(compare_file),
+        None => {
+            println!("End of script, no compare file.");
+            return;
+        }
+    };
+    let compare_text = match fs::read_to_string(&compare_file) {
+        Ok(text) => text,
+        Err(why) => {
+            eprintln!("[ERROR] couldn't open {}: {}", compare_file.display(), why);
+            process::exit(1);
+        }
+    };
+    match compare_output(&run.output, &compare_text) {
+        None => println!("End of script - Comparison ended successfully"),
+        Some(failure) => {
+            eprintln!("[ERROR] comparison failure at line {}", failure.line);
+            let or_missing = |line: &str| match line {
+                "" => "(no line)".to_string(),
+                line => line.to_string(),
+            };
+            eprintln!("    expected: {}", or_missing(&failure.expected));
+            eprintln!("    actual:   {}", or_missing(&failure.actual));
+            process::exit(1);
+        }
+    }
+}
+
+/// Returns the path given with `--dir` or `--file`.
+fn path_option(args: &[String]) -> (String, PathType) {
+    let dir_option = find_option(args, "--dir");
+    let file_option = find_option(args, "--file");
 
     let path_str: String;
 
     let path_type: PathType;
     // check if file path exists and has vm extension:
-    if !(is_dir_option ^ is_file_option) {
-        eprintln!("[ERROR] incorrect arguments.");
-        // TODO: print help
-        panic!();
-    } else {
-        if is_dir_option {
-            let option_index = args.iter().position(|i| *i == "--dir").unwrap();
-            let dir_index = option_index + 1;
-            path_str = args[dir_index].clone();
+    match (dir_option, file_option) {
+        (Some(dir), None) => {
+            path_str = dir;
             path_type = PathType::Dir;
-        } else {
-            // file_option
-            let option_index = args.iter().position(|i| *i == "--file").unwrap();
-            let file_index = option_index + 1;
-            path_str = args[file_index].clone();
+        }
+        (None, Some(file)) => {
+            path_str = file;
             path_type = PathType::File;
         }
+        _ => exit_with_usage("incorrect arguments."),
     }
 
-    let path = Path::new(&path_str);
-    if !path.exists() {
-        eprintln!(
+    if !Path::new(&path_str).exists() {
+        exit_with_usage(&format!(
             "Path {} does not exist or couldn't be parsed correctly.",
             path_str
-        );
-        // TODO: print help / usage
-        panic!();
+        ));
+    }
+    return (path_str, path_type);
+}
+
+/// Builds the translator options from the config file (if any), then the command line.
+fn translate_options(args: &[String]) -> TranslateOptions {
+    let is_debug_option = args.contains(&"--debug".to_string());
+
+    let error_limit: Option<usize> =
+        find_option(args, "--error-limit").map(|value| match value.parse() {
+            Ok(n) if n > 0 => n,
+            _ => exit_with_usage("--error-limit expects a positive integer."),
+        });
+
+    let mut options = TranslateOptions {
+        is_debug_option,
+        error_limit,
+        ..Default::default()
+    };
+
+    // the config file is applied first so that command line options take precedence.
+    if let Some(config_path) = find_option(args, "--config") {
+        let text = match fs::read_to_string(&config_path) {
+            Ok(text) => text,
+            Err(why) => exit_with_usage(&format!("couldn't open {}: {}", config_path, why)),
+        };
+        if let Err(message) = apply_config(&text, &mut options) {
+            exit_with_usage(&format!("{}: {}", config_path, message));
+        }
+    }
+    if args.contains(&"--global-labels".to_string()) {
+        options.is_global_labels_option = true;
     }
+    if args.contains(&"--shared-calls".to_string()) {
+        options.is_shared_calls_option = true;
+    }
+    if args.contains(&"--tail-calls".to_string()) {
+        options.is_tail_calls_option = true;
+    }
+    if args.contains(&"--shared-comparisons".to_string()) {
+        options.is_shared_comparisons_option = true;
+    }
+    if args.contains(&"--eliminate-dead-functions".to_string()) {
+        options.is_eliminate_dead_functions_option = true;
+    }
+    if args.contains(&"-O2".to_string()) {
+        options.optimization_level = 2;
+    } else if args.contains(&"-O1".to_string()) {
+        options.optimization_level = 1;
+    } else if args.contains(&"-O0".to_string()) {
+        options.optimization_level = 0;
+    }
+    for key in TRANSLATE_OPTIONS {
+        if let Some(value) = find_option(args, &format!("--{}", key)) {
+            if let Err(message) = set_option(&mut options, key, &value) {
+                exit_with_usage(&message);
+            }
+        }
+    }
+    return options;
+}
 
-    let mut code_gen = CodeGenerator::new(&path, &path_type, is_debug_option);
-    code_gen.generate_code();
+/// The vm files as translated with the given options: with small functions inlined if
+/// `--inline` is given, without dead functions if `--eliminate-dead-functions` is given,
+/// optimized at `-O2`.
+fn optimized_files(program: VmProgram, options: &TranslateOptions) -> Vec<VmFile> {
+    let mut files = program.files;
+    if let Some(max_size) = options.inline_threshold {
+        files = inline_functions(&files, max_size);
+    }
+    if options.is_eliminate_dead_functions_option {
+        files = eliminate_dead_functions(&files, options).0;
+    }
+    if options.optimization_level >= 2 {
+        files = optimize_files(&files);
+    }
+    return files;
+}
+
+/// Reads and translates the vm files, printing the diagnostics.
+///
+/// Exits with a non-zero code if any error occurred.
+fn translate_or_exit(path: &Path, path_type: &PathType, options: &TranslateOptions) -> AsmProgram {
+    let sources = read_sources_or_exit(path, path_type);
+    let program = match translate(&sources, options) {
+        Ok(program) => program,
+        Err(e) => {
+            // no output is produced if any error occurred.
+            eprint!("{}", e);
+            process::exit(1);
+        }
+    };
+    for warning in &program.warnings {
+        eprint!("{}", warning);
+    }
+    return program;
+}
+
+/// Reads and parses the vm files, printing the diagnostics.
+///
+/// Exits with a non-zero code if any error occurred.
+fn parse_or_exit(path: &Path, path_type: &PathType, options: &TranslateOptions) -> VmProgram {
+    let sources = read_sources_or_exit(path, path_type);
+    let program = match parse_program(&sources, options) {
+        Ok(program) => program,
+        Err(e) => {
+            eprint!("{}", e);
+            process::exit(1);
+        }
+    };
+    for warning in &program.warnings {
+        eprint!("{}", warning);
+    }
+    return program;
+}
+
+/// Reads the vm file, or every vm file of the directory.
+fn read_sources_or_exit(path: &Path, path_type: &PathType) -> Vec<VmSource> {
+    let mut files_vec: Vec<PathBuf> = vec![];
+    match path_type {
+        PathType::File => files_vec.push(path.to_path_buf()),
+        PathType::Dir => search_vm_files(path, &mut files_vec),
+    }
+
+    let mut sources: Vec<VmSource> = vec![];
+    for file_path in &files_vec {
+        match VmSource::from_path(file_path) {
+            Ok(source) => sources.push(source),
+            Err(why) => {
+                eprintln!("couldn't open {}: {}", file_path.display(), why);
+                process::exit(1);
+            }
+        }
+    }
+    return sources;
+}
+
+/// Returns the number of vm commands given with `--steps`.
+fn steps_option(args: &[String]) -> u64 {
+    return match find_option(args, "--steps") {
+        Some(value) => match value.parse() {
+            Ok(n) => n,
+            Err(_) => exit_with_usage("--steps expects a positive integer."),
+        },
+        None => DEFAULT_CYCLES,
+    };
+}
+
+/// Returns the RAM cells given with `--ram` (default: 0-15).
+fn ram_cells_option(args: &[String]) -> Vec<usize> {
+    return match find_option(args, "--ram") {
+        Some(value) => parse_ram_cells(&value),
+        None => (0..16).collect(),
+    };
+}
+
+/// Returns the RAM values given with `--set`, e.g. `0=256,1=300`.
+fn ram_assignments(args: &[String]) -> Vec<(usize, i16)> {
+    let mut assignments: Vec<(usize, i16)> = vec![];
+    if let Some(value) = find_option(args, "--set") {
+        for assignment in value.split(',') {
+            let parsed = assignment.split_once('=').and_then(|(cell, value)| {
+                Some((
+                    cell.trim().parse::<usize>().ok()?,
+                    value.trim().parse::<i16>().ok()?,
+                ))
+            });
+            match parsed {
+                Some((cell, value)) if cell < RAM_SIZE => assignments.push((cell, value)),
+                _ => exit_with_usage(&format!("--set: cannot parse `{}`.", assignment)),
+            }
+        }
+    }
+    return assignments;
+}
+
+/// Parses a list of RAM cells such as `0,256-260`.
+fn parse_ram_cells(value: &str) -> Vec<usize> {
+    let mut cells: Vec<usize> = vec![];
+    for part in value.split(',') {
+        let range: Option<(usize, usize)> = match part.split_once('-') {
+            Some((first, last)) => first.trim().parse().ok().zip(last.trim().parse().ok()),
+            None => part.trim().parse().ok().map(|cell| (cell, cell)),
+        };
+        match range {
+            Some((first, last)) if first <= last && last < RAM_SIZE => cells.extend(first..=last),
+            _ => exit_with_usage(&format!("--ram: cannot parse `{}`.", part)),
+        }
+    }
+    return cells;
+}
+
+/// Returns the value of `option`, given either as `--option value` or `--option=value`.
+fn find_option(args: &[String], option: &str) -> Option<String> {
+    let prefix = format!("{}=", option);
+    for (i, arg) in args.iter().enumerate() {
+        if arg == option {
+            return match args.get(i + 1) {
+                Some(value) => Some(value.clone()),
+                None => exit_with_usage(&format!("{} expects a value.", option)),
+            };
+        }
+        if let Some(value) = arg.strip_prefix(&prefix) {
+            return Some(value.to_string());
+        }
+    }
+    return None;
 }
@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+
+use crate::assembler::Instruction;
+
+/// Instruction counts of a program before and after `optimize`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizerReport {
+    pub instructions_before: usize,
+    pub instructions_after: usize,
+}
+
+/// Registers the generated code only uses as scratch within a single vm command.
+const SCRATCH_REGISTERS: [u16; 2] = [13, 14];
+
+/// Peephole optimizer over generated assembly. Applies the rules below until none matches:
+///
+/// - `@SP, M=M+1, @SP, M=M-1` (a push followed by a pop) becomes `@SP`, and
+///   `@SP, M=M+1, @SP, AM=M-1` becomes `@SP, A=M`;
+/// - `@SP, A=M, M=D, @SP, A=M, D=M` (reloading the value just stored) drops the reload;
+/// - `@X` when A already holds X, or when the next instruction loads A again, is removed;
+/// - stores to R13/R14 overwritten before being read are removed;
+/// - jumps to the next instruction are removed.
+///
+/// Rules never look across labels, except for jumps to the next instruction.
+pub fn optimize(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut instructions = instructions.to_vec();
+    loop {
+        let before = instructions.len();
+        instructions = remove_push_pop_pairs(&instructions);
+        instructions = remove_redundant_loads(&instructions);
+        instructions = remove_dead_scratch_stores(&instructions);
+        instructions = remove_jumps_to_next(&instructions);
+        if instructions.len() == before {
+            return instructions;
+        }
+    }
+}
+
+/// Returns the instruction counts (labels excluded) of a program before and after optimizing it.
+pub fn report(before: &[Instruction], after: &[Instruction]) -> OptimizerReport {
+    let count =
+        |instructions: &[Instruction]| instructions.iter().filter(|i| i.is_instruction()).count();
+    return OptimizerReport {
+        instructions_before: count(before),
+        instructions_after: count(after),
+    };
+}
+
+/// Value loaded by an A-instruction, with the predefined symbols `SP`..`THAT` and `R0`..`R15`
+/// resolved so that `@SP` and `@0` compare equal.
+fn loaded_value(instruction: &Instruction) -> Option<Instruction> {
+    return match instruction {
+        Instruction::AConst(_) => Some(instruction.clone()),
+        Instruction::ASymbol(symbol) => {
+            let address = match symbol.as_str() {
+                "SP" => Some(0),
+                "LCL" => Some(1),
+                "ARG" => Some(2),
+                "THIS" => Some(3),
+                "THAT" => Some(4),
+                _ => symbol
+                    .strip_prefix('R')
+                    .and_then(|n| n.parse::<u16>().ok())
+                    .filter(|n| *n < 16),
+            };
+            Some(match address {
+                Some(address) => Instruction::AConst(address),
+                None => instruction.clone(),
+            })
+        }
+        _ => None,
+    };
+}
+
+fn is_c(instruction: &Instruction, dest: &str, comp: &str) -> bool {
+    return *instruction == Instruction::c(dest, comp, "");
+}
+
+fn is_sp(instruction: &Instruction) -> bool {
+    return loaded_value(instruction) == Some(Instruction::AConst(0));
+}
+
+fn remove_push_pop_pairs(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut optimized: Vec<Instruction> = vec![];
+    let mut i = 0;
+    while i < instructions.len() {
+        let window = &instructions[i..instructions.len().min(i + 6)];
+        let is_increment_then = |comp_dest: &str| {
+            window.len() >= 4
+                && is_sp(&window[0])
+                && is_c(&window[1], "M", "M+1")
+                && is_sp(&window[2])
+                && is_c(&window[3], comp_dest, "M-1")
+        };
+        if is_increment_then("M") {
+            optimized.push(window[0].clone());
+            i += 4;
+        } else if is_increment_then("AM") {
+            optimized.push(window[0].clone());
+            optimized.push(Instruction::c("A", "M", ""));
+            i += 4;
+        } else if window.len() == 6
+            && is_sp(&window[0])
+            && is_c(&window[1], "A", "M")
+            && is_c(&window[2], "M", "D")
+            && is_sp(&window[3])
+            && is_c(&window[4], "A", "M")
+            && is_c(&window[5], "D", "M")
+        {
+            optimized.extend_from_slice(&window[..3]);
+            i += 6;
+        } else {
+            optimized.push(instructions[i].clone());
+            i += 1;
+        }
+    }
+    return optimized;
+}
+
+fn remove_redundant_loads(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut optimized: Vec<Instruction> = vec![];
+    let mut known_a: Option<Instruction> = None;
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::Label(_) => known_a = None,
+            Instruction::AConst(_) | Instruction::ASymbol(_) => {
+                let value = loaded_value(instruction);
+                let is_overwritten = matches!(
+                    instructions.get(i + 1),
+                    Some(Instruction::AConst(_) | Instruction::ASymbol(_))
+                );
+                if value == known_a || is_overwritten {
+                    continue;
+                }
+                known_a = value;
+            }
+            Instruction::C { dest, .. } => {
+                if dest.contains('A') {
+                    known_a = None;
+                }
+            }
+        }
+        optimized.push(instruction.clone());
+    }
+    return optimized;
+}
+
+/// A store `M=...` to R13/R14 is dead if the same register is stored again before being
+/// read, within straight-line code.
+fn remove_dead_scratch_stores(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut dead: HashSet<usize> = HashSet::new();
+    let mut known_a: Option<u16> = None;
+    for (i, instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::Label(_) => known_a = None,
+            Instruction::AConst(_) | Instruction::ASymbol(_) => {
+                known_a = match loaded_value(instruction) {
+                    Some(Instruction::AConst(address)) => Some(address),
+                    _ => None,
+                };
+            }
+            Instruction::C { dest, comp, jump } => {
+                let is_scratch_store = dest == "M"
+                    && !comp.contains('M')
+                    && jump.is_empty()
+                    && known_a.is_some_and(|a| SCRATCH_REGISTERS.contains(&a));
+                if is_scratch_store && is_overwritten_before_read(instructions, i, known_a.unwrap())
+                {
+                    dead.insert(i);
+                }
+                if dest.contains('A') {
+                    known_a = None;
+                }
+            }
+        }
+    }
+    return instructions
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !dead.contains(i))
+        .map(|(_, instruction)| instruction.clone())
+        .collect();
+}
+
+fn is_overwritten_before_read(instructions: &[Instruction], store: usize, register: u16) -> bool {
+    let mut known_a: Option<u16> = Some(register);
+    for instruction in &instructions[store + 1..] {
+        match instruction {
+            Instruction::Label(_) => return false,
+            Instruction::AConst(_) | Instruction::ASymbol(_) => {
+                known_a = match loaded_value(instruction) {
+                    Some(Instruction::AConst(address)) => Some(address),
+                    // labels and variables never alias the scratch registers.
+                    _ => Some(u16::MAX),
+                };
+            }
+            Instruction::C { dest, comp, jump } => {
+                let is_register = known_a.is_none_or(|a| a == register);
+                if comp.contains('M') && is_register {
+                    return false;
+                }
+                if !jump.is_empty() {
+                    return false;
+                }
+                if dest.contains('M') && known_a == Some(register) {
+                    return true;
+                }
+                if dest.contains('A') {
+                    known_a = None;
+                }
+            }
+        }
+    }
+    return false;
+}
+
+/// Removes `@L, ;JMP` (any jump without destination) directly followed by `(L)`, when the
+/// code after the label loads A before using it.
+fn remove_jumps_to_next(instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut optimized: Vec<Instruction> = vec![];
+    let mut i = 0;
+    while i < instructions.len() {
+        if let (Instruction::ASymbol(target), Some(Instruction::C { dest, jump, .. })) =
+            (&instructions[i], instructions.get(i + 1))
+        {
+            let labels_end = instructions[i + 2..]
+                .iter()
+                .position(|instruction| instruction.is_instruction())
+                .map_or(instructions.len(), |n| i + 2 + n);
+            let is_to_next = instructions[i + 2..labels_end]
+                .iter()
+                .any(|label| *label == Instruction::Label(target.clone()));
+            let is_a_reloaded = matches!(
+                instructions.get(labels_end),
+                None | Some(Instruction::AConst(_) | Instruction::ASymbol(_))
+            );
+            if dest.is_empty() && !jump.is_empty() && is_to_next && is_a_reloaded {
+                i += 2;
+                continue;
+            }
+        }
+        optimized.push(instructions[i].clone());
+        i += 1;
+    }
+    return optimized;
+}
@@ -0,0 +1,399 @@
+use crate::VmSource;
+use std::fmt;
+
+/// Largest value that fits in a HACK A-instruction (15 bits).
+const MAX_INDEX: u16 = 32767;
+
+/// Position of a token in a vm file. `line` and `column` are 1-based, `len` is
+/// the length of the token in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Segment {
+    Local,
+    Argument,
+    Constant,
+    This,
+    That,
+    Static,
+    Pointer,
+    Temp,
+    /// Directly access any RAM[n]. Not part of the vm specification, be careful when using this.
+    General,
+}
+
+impl Segment {
+    pub fn from_name(s: &str) -> Option<Segment> {
+        let segment = match s {
+            "local" => Segment::Local,
+            "argument" => Segment::Argument,
+            "constant" => Segment::Constant,
+            "this" => Segment::This,
+            "that" => Segment::That,
+            "static" => Segment::Static,
+            "pointer" => Segment::Pointer,
+            "temp" => Segment::Temp,
+            "general" => Segment::General,
+            _ => return None,
+        };
+        return Some(segment);
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Segment::Local => "local",
+            Segment::Argument => "argument",
+            Segment::Constant => "constant",
+            Segment::This => "this",
+            Segment::That => "that",
+            Segment::Static => "static",
+            Segment::Pointer => "pointer",
+            Segment::Temp => "temp",
+            Segment::General => "general",
+        };
+    }
+}
+
+/// Arithmetic / logical commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArithmeticOp {
+    Add,
+    Sub,
+    Neg,
+    Eq,
+    Gt,
+    Lt,
+    And,
+    Or,
+    Not,
+}
+
+impl ArithmeticOp {
+    pub const ALL: [ArithmeticOp; 9] = [
+        ArithmeticOp::Add,
+        ArithmeticOp::Sub,
+        ArithmeticOp::Neg,
+        ArithmeticOp::Eq,
+        ArithmeticOp::Gt,
+        ArithmeticOp::Lt,
+        ArithmeticOp::And,
+        ArithmeticOp::Or,
+        ArithmeticOp::Not,
+    ];
+
+    pub fn from_name(s: &str) -> Option<ArithmeticOp> {
+        return ArithmeticOp::ALL.iter().copied().find(|op| op.name() == s);
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            ArithmeticOp::Add => "add",
+            ArithmeticOp::Sub => "sub",
+            ArithmeticOp::Neg => "neg",
+            ArithmeticOp::Eq => "eq",
+            ArithmeticOp::Gt => "gt",
+            ArithmeticOp::Lt => "lt",
+            ArithmeticOp::And => "and",
+            ArithmeticOp::Or => "or",
+            ArithmeticOp::Not => "not",
+        };
+    }
+
+    /// `neg` and `not` operate on one value only.
+    pub fn is_unary(&self) -> bool {
+        return matches!(self, ArithmeticOp::Neg | ArithmeticOp::Not);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmCommand {
+    Push {
+        segment: Segment,
+        index: u16,
+    },
+    Pop {
+        segment: Segment,
+        index: u16,
+    },
+    Arithmetic(ArithmeticOp),
+    Label(String),
+    Goto(String),
+    IfGoto(String),
+    Function {
+        name: String,
+        n_vars: u16,
+    },
+    Call {
+        name: String,
+        n_args: u16,
+    },
+    Return,
+    /// `push source source_index` directly followed by `pop segment index`, without going
+    /// through the stack. Never produced by the parser, only by `vm_optimizer`.
+    Move {
+        source: Segment,
+        source_index: u16,
+        segment: Segment,
+        index: u16,
+    },
+}
+
+impl fmt::Display for VmCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmCommand::Push { segment, index } => write!(f, "push {} {}", segment.name(), index),
+            VmCommand::Pop { segment, index } => write!(f, "pop {} {}", segment.name(), index),
+            VmCommand::Arithmetic(op) => write!(f, "{}", op.name()),
+            VmCommand::Label(label) => write!(f, "label {}", label),
+            VmCommand::Goto(label) => write!(f, "goto {}", label),
+            VmCommand::IfGoto(label) => write!(f, "if-goto {}", label),
+            VmCommand::Function { name, n_vars } => write!(f, "function {} {}", name, n_vars),
+            VmCommand::Call { name, n_args } => write!(f, "call {} {}", name, n_args),
+            VmCommand::Return => write!(f, "return"),
+            VmCommand::Move {
+                source,
+                source_index,
+                segment,
+                index,
+            } => write!(
+                f,
+                "move {} {} to {} {}",
+                source.name(),
+                source_index,
+                segment.name(),
+                index
+            ),
+        }
+    }
+}
+
+/// A vm command together with its position (from the keyword to the last argument).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedCommand {
+    pub command: VmCommand,
+    pub span: Span,
+}
+
+/// All commands of a single vm file.
+#[derive(Debug, Clone)]
+pub struct VmFile {
+    pub name: String,
+    pub commands: Vec<ParsedCommand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub span: Span,
+    pub message: String,
+}
+
+/// A word of a vm line and its position.
+struct Token<'a> {
+    text: &'a str,
+    span: Span,
+}
+
+/// Splits a line in words (operators and arguments), ignoring comments.
+fn tokenize(line: &str, line_number: usize) -> Vec<Token<'_>> {
+    let code = match line.find("//") {
+        Some(i) => &line[..i],
+        None => line,
+    };
+
+    let mut tokens: Vec<Token> = vec![];
+    let mut start: Option<usize> = None;
+    for (i, c) in code.char_indices().chain([(code.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(s)) => {
+                tokens.push(Token {
+                    text: &code[s..i],
+                    span: Span {
+                        line: line_number,
+                        column: code[..s].chars().count() + 1,
+                        len: code[s..i].chars().count(),
+                    },
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    return tokens;
+}
+
+/// Parses the content of a vm file.
+///
+/// Recovers at line granularity: a line that cannot be parsed is skipped and
+/// parsing continues with the next one, so every error of the file is returned.
+pub fn parse(source: &VmSource) -> Result<VmFile, Vec<ParseError>> {
+    let mut commands: Vec<ParsedCommand> = vec![];
+    let mut errors: Vec<ParseError> = vec![];
+    for (line_index, line) in source.text.lines().enumerate() {
+        match parse_line(line, line_index + 1) {
+            Ok(Some(command)) => commands.push(command),
+            Ok(None) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    return Ok(VmFile {
+        name: source.name.clone(),
+        commands,
+    });
+}
+
+/// Parses a single line. Returns `None` for empty lines and comments.
+pub fn parse_line(line: &str, line_number: usize) -> Result<Option<ParsedCommand>, ParseError> {
+    let tokens = tokenize(line, line_number);
+    let keyword = match tokens.first() {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+
+    let expect_args = |n: usize, what: &str| -> Result<(), ParseError> {
+        if tokens.len() < n + 1 {
+            let end = tokens.last().unwrap().span;
+            return Err(ParseError {
+                span: Span {
+                    line: line_number,
+                    column: end.column + end.len,
+                    len: 1,
+                },
+                message: format!("`{}` expects {}.", keyword.text, what),
+            });
+        }
+        if tokens.len() > n + 1 {
+            return Err(ParseError {
+                span: tokens[n + 1].span,
+                message: format!("Unexpected token `{}`.", tokens[n + 1].text),
+            });
+        }
+        return Ok(());
+    };
+
+    let command = match keyword.text {
+        "push" | "pop" => {
+            expect_args(2, "a memory segment and an index")?;
+            let segment = match Segment::from_name(tokens[1].text) {
+                Some(segment) => segment,
+                None => {
+                    return Err(ParseError {
+                        span: tokens[1].span,
+                        message: format!(
+                            "Memory segment {} does not exist or is not supported.",
+                            tokens[1].text
+                        ),
+                    })
+                }
+            };
+            let index = parse_number(&tokens[2])?;
+            if segment == Segment::Pointer && index > 1 {
+                return Err(ParseError {
+                    span: tokens[2].span,
+                    message: format!("pointer {} is out of range (0 or 1).", index),
+                });
+            }
+            if segment == Segment::Temp && index > 7 {
+                return Err(ParseError {
+                    span: tokens[2].span,
+                    message: format!("temp {} is out of range (0 to 7).", index),
+                });
+            }
+            if keyword.text == "push" {
+                VmCommand::Push { segment, index }
+            } else {
+                if segment == Segment::Constant {
+                    return Err(ParseError {
+                        span: tokens[1].span,
+                        message: "Cannot pop a constant.".to_string(),
+                    });
+                }
+                VmCommand::Pop { segment, index }
+            }
+        }
+        "label" | "goto" | "if-goto" => {
+            expect_args(1, "a label")?;
+            let label = parse_symbol(&tokens[1])?;
+            match keyword.text {
+                "label" => VmCommand::Label(label),
+                "goto" => VmCommand::Goto(label),
+                _ => VmCommand::IfGoto(label),
+            }
+        }
+        "function" | "call" => {
+            expect_args(2, "a function name and a number")?;
+            let name = parse_symbol(&tokens[1])?;
+            let n = parse_number(&tokens[2])?;
+            if keyword.text == "function" {
+                VmCommand::Function { name, n_vars: n }
+            } else {
+                VmCommand::Call { name, n_args: n }
+            }
+        }
+        "return" => {
+            expect_args(0, "no arguments")?;
+            VmCommand::Return
+        }
+        text => match ArithmeticOp::from_name(text) {
+            Some(op) => {
+                expect_args(0, "no arguments")?;
+                VmCommand::Arithmetic(op)
+            }
+            None => {
+                return Err(ParseError {
+                    span: keyword.span,
+                    message: format!("Line starts with unknown keyword `{}`.", text),
+                })
+            }
+        },
+    };
+
+    let last = tokens.last().unwrap().span;
+    return Ok(Some(ParsedCommand {
+        command,
+        span: Span {
+            line: line_number,
+            column: keyword.span.column,
+            len: last.column + last.len - keyword.span.column,
+        },
+    }));
+}
+
+fn parse_number(token: &Token) -> Result<u16, ParseError> {
+    return match token.text.parse::<u16>() {
+        Ok(n) if n <= MAX_INDEX => Ok(n),
+        _ => Err(ParseError {
+            span: token.span,
+            message: format!(
+                "{} cannot be parsed as an unsigned integer between 0 and {}.",
+                token.text, MAX_INDEX
+            ),
+        }),
+    };
+}
+
+/// Labels and function names: a sequence of letters, digits, `_`, `.`, `$` and `:`
+/// that does not begin with a digit.
+fn parse_symbol(token: &Token) -> Result<String, ParseError> {
+    let is_valid = !token.text.starts_with(|c: char| c.is_ascii_digit())
+        && token
+            .text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.$:".contains(c));
+    if !is_valid {
+        return Err(ParseError {
+            span: token.span,
+            message: format!("{} is not a valid symbol.", token.text),
+        });
+    }
+    return Ok(token.text.to_string());
+}
@@ -0,0 +1,263 @@
+use std::fmt::Write;
+
+use crate::assembler::rom_size;
+use crate::budget::{function_code, measure_startup, FunctionSize};
+use crate::diagnostic::Diagnostic;
+use crate::parser::{VmCommand, VmFile};
+
+/// Groups of vm commands the cost profile breaks instructions down by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandKind {
+    /// `push`, and the initialization of the locals by `function`.
+    Push,
+    /// `pop`, and the push/pop pairs fused by the vm optimizer.
+    Pop,
+    Arithmetic,
+    Call,
+    Return,
+    /// `label`, `goto` and `if-goto`.
+    Branch,
+}
+
+impl CommandKind {
+    pub const ALL: [CommandKind; 6] = [
+        CommandKind::Push,
+        CommandKind::Pop,
+        CommandKind::Arithmetic,
+        CommandKind::Call,
+        CommandKind::Return,
+        CommandKind::Branch,
+    ];
+
+    pub fn of(command: &VmCommand) -> CommandKind {
+        return match command {
+            VmCommand::Push { .. } | VmCommand::Function { .. } => CommandKind::Push,
+            VmCommand::Pop { .. } | VmCommand::Move { .. } => CommandKind::Pop,
+            VmCommand::Arithmetic(_) => CommandKind::Arithmetic,
+            VmCommand::Call { .. } => CommandKind::Call,
+            VmCommand::Return => CommandKind::Return,
+            VmCommand::Label(_) | VmCommand::Goto(_) | VmCommand::IfGoto(_) => CommandKind::Branch,
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            CommandKind::Push => "push",
+            CommandKind::Pop => "pop",
+            CommandKind::Arithmetic => "arith",
+            CommandKind::Call => "call",
+            CommandKind::Return => "return",
+            CommandKind::Branch => "branch",
+        };
+    }
+}
+
+/// Output format of `--stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl StatsFormat {
+    pub fn from_name(s: &str) -> Option<StatsFormat> {
+        return match s {
+            "table" => Some(StatsFormat::Table),
+            "json" => Some(StatsFormat::Json),
+            "csv" => Some(StatsFormat::Csv),
+            _ => None,
+        };
+    }
+}
+
+/// Instructions generated for the commands of one function, by command kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCost {
+    pub size: FunctionSize,
+    pub vm_commands: usize,
+    /// Instructions per `CommandKind`, in the order of `CommandKind::ALL`.
+    pub instructions_by_kind: [usize; 6],
+}
+
+/// Every generated instruction attributed to its vm command and function.
+///
+/// The instructions are counted as generated, before the peephole optimizer, which works
+/// across commands.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CostProfile {
+    /// Instructions of the shared routines and the bootstrap.
+    pub startup_instructions: usize,
+    /// Every function, the most expensive first.
+    pub functions: Vec<FunctionCost>,
+}
+
+impl CostProfile {
+    /// `code_blocks` are the blocks returned by `CodeGenerator::generate_code_blocks` for `files`.
+    /// Returns an error if some of the assembly doesn't parse.
+    pub fn new(files: &[VmFile], code_blocks: &[Vec<String>]) -> Result<CostProfile, Diagnostic> {
+        let mut profile = CostProfile {
+            startup_instructions: measure_startup(files, code_blocks, |blocks| {
+                rom_size(&blocks.concat())
+            })?,
+            functions: vec![],
+        };
+        for code in function_code(files, code_blocks) {
+            let mut instructions_by_kind = [0; 6];
+            let size = code.measure(|blocks| {
+                let mut instructions = 0;
+                for (parsed, block) in code.commands.iter().zip(blocks) {
+                    let kind = CommandKind::of(&parsed.command);
+                    let kind_index = CommandKind::ALL.iter().position(|k| *k == kind).unwrap();
+                    let block_instructions = rom_size(block)?;
+                    instructions_by_kind[kind_index] += block_instructions;
+                    instructions += block_instructions;
+                }
+                return Ok(instructions);
+            })?;
+            profile.functions.push(FunctionCost {
+                size,
+                vm_commands: code.commands.len(),
+                instructions_by_kind,
+            });
+        }
+        // stable sort: functions of the same cost stay in program order.
+        profile
+            .functions
+            .sort_by_key(|cost| std::cmp::Reverse(cost.size.instructions));
+        return Ok(profile);
+    }
+
+    pub fn total_instructions(&self) -> usize {
+        let functions: usize = self
+            .functions
+            .iter()
+            .map(|cost| cost.size.instructions)
+            .sum();
+        return self.startup_instructions + functions;
+    }
+
+    pub fn render(&self, format: StatsFormat) -> String {
+        return match format {
+            StatsFormat::Table => self.to_table(),
+            StatsFormat::Json => self.to_json(),
+            StatsFormat::Csv => self.to_csv(),
+        };
+    }
+
+    fn to_table(&self) -> String {
+        let name_width = self
+            .functions
+            .iter()
+            .map(|cost| cost.size.name().len())
+            .chain(["function".len()])
+            .max()
+            .unwrap_or_default();
+        let file_width = self
+            .functions
+            .iter()
+            .map(|cost| cost.size.file.len())
+            .chain(["file".len()])
+            .max()
+            .unwrap_or_default();
+
+        let mut table = format!(
+            "{:<name_width$}  {:<file_width$}  {:>6}  {:>6}",
+            "function", "file", "vm", "asm"
+        );
+        for kind in CommandKind::ALL {
+            let _ = write!(table, "  {:>6}", kind.name());
+        }
+        table.push('\n');
+        for cost in &self.functions {
+            let _ = write!(
+                table,
+                "{:<name_width$}  {:<file_width$}  {:>6}  {:>6}",
+                cost.size.name(),
+                cost.size.file,
+                cost.vm_commands,
+                cost.size.instructions
+            );
+            for instructions in cost.instructions_by_kind {
+                let _ = write!(table, "  {:>6}", instructions);
+            }
+            table.push('\n');
+        }
+        let _ = writeln!(
+            table,
+            "bootstrap and shared routines: {} instructions",
+            self.startup_instructions
+        );
+        let _ = writeln!(table, "total: {} instructions", self.total_instructions());
+        return table;
+    }
+
+    fn to_json(&self) -> String {
+        let functions: Vec<String> = self
+            .functions
+            .iter()
+            .map(|cost| {
+                let function = match &cost.size.function {
+                    Some(function) => format!("\"{}\"", json_escape(function)),
+                    None => "null".to_string(),
+                };
+                let by_kind: Vec<String> = CommandKind::ALL
+                    .iter()
+                    .zip(cost.instructions_by_kind)
+                    .map(|(kind, instructions)| format!("\"{}\": {}", kind.name(), instructions))
+                    .collect();
+                format!(
+                    "    {{\"function\": {}, \"file\": \"{}\", \"vm_commands\": {}, \"instructions\": {}, \"by_kind\": {{{}}}}}",
+                    function,
+                    json_escape(&cost.size.file),
+                    cost.vm_commands,
+                    cost.size.instructions,
+                    by_kind.join(", ")
+                )
+            })
+            .collect();
+        return format!(
+            "{{\n  \"startup_instructions\": {},\n  \"total_instructions\": {},\n  \"functions\": [\n{}\n  ]\n}}\n",
+            self.startup_instructions,
+            self.total_instructions(),
+            functions.join(",\n")
+        );
+    }
+
+    /// One row per function; the bootstrap and shared routines are not included.
+    fn to_csv(&self) -> String {
+        let mut csv = "function,file,vm_commands,instructions".to_string();
+        for kind in CommandKind::ALL {
+            csv.push(',');
+            csv.push_str(kind.name());
+        }
+        csv.push('\n');
+        for cost in &self.functions {
+            let _ = write!(
+                csv,
+                "{},{},{},{}",
+                csv_field(&cost.size.function.clone().unwrap_or_default()),
+                csv_field(&cost.size.file),
+                cost.vm_commands,
+                cost.size.instructions
+            );
+            for instructions in cost.instructions_by_kind {
+                let _ = write!(csv, ",{}", instructions);
+            }
+            csv.push('\n');
+        }
+        return csv;
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    return s.replace('\\', "\\\\").replace('"', "\\\"");
+}
+
+/// Quotes a field containing a comma or a quote.
+fn csv_field(s: &str) -> String {
+    if s.contains(',') || s.contains('"') {
+        return format!("\"{}\"", s.replace('"', "\"\""));
+    }
+    return s.to_string();
+}
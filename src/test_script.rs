@@ -0,0 +1,431 @@
+use std::fmt;
+
+use crate::hack_cpu::{HackCpu, RAM_SIZE};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    /// 1-based line of the test script.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(
+            f,
+            "[ERROR] test script line {}: {}",
+            self.line, self.message
+        );
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// A register or RAM cell of the CPU emulator, as named in test scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptVariable {
+    Ram(usize),
+    A,
+    D,
+    PC,
+}
+
+impl ScriptVariable {
+    /// Parses `RAM[n]`, `A`, `D` or `PC`.
+    pub fn from_name(name: &str) -> Option<ScriptVariable> {
+        return match name {
+            "A" => Some(ScriptVariable::A),
+            "D" => Some(ScriptVariable::D),
+            "PC" => Some(ScriptVariable::PC),
+            _ => {
+                let address: usize = name.strip_prefix("RAM[")?.strip_suffix(']')?.parse().ok()?;
+                if address < RAM_SIZE {
+                    Some(ScriptVariable::Ram(address))
+                } else {
+                    None
+                }
+            }
+        };
+    }
+
+    fn get(&self, cpu: &HackCpu) -> u16 {
+        return match self {
+            ScriptVariable::Ram(address) => cpu.ram[*address],
+            ScriptVariable::A => cpu.a,
+            ScriptVariable::D => cpu.d,
+            ScriptVariable::PC => cpu.pc,
+        };
+    }
+
+    fn set(&self, cpu: &mut HackCpu, value: u16) {
+        match self {
+            ScriptVariable::Ram(address) => cpu.ram[*address] = value,
+            ScriptVariable::A => cpu.a = value,
+            ScriptVariable::D => cpu.d = value,
+            ScriptVariable::PC => cpu.pc = value,
+        }
+    }
+}
+
+/// A column of `output-list`, e.g. `RAM[256]%D2.6.2`: the variable, its format
+/// (`D`ecimal, he`X`adecimal or `B`inary), then the left padding, width and right padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputColumn {
+    pub name: String,
+    pub variable: ScriptVariable,
+    pub format: char,
+    pub pad_left: usize,
+    pub len: usize,
+    pub pad_right: usize,
+}
+
+impl OutputColumn {
+    /// Parses `name%Fl.n.r`. Without a format, `%B1.16.1` is used like the CPU emulator.
+    pub fn parse(text: &str) -> Result<OutputColumn, String> {
+        let (name, format) = text.split_once('%').unwrap_or((text, "B1.16.1"));
+        let variable = match ScriptVariable::from_name(name) {
+            Some(variable) => variable,
+            None => return Err(format!("unknown variable `{}`.", name)),
+        };
+        let mut chars = format.chars();
+        let format_char = chars.next().unwrap_or('B');
+        let widths: Vec<usize> = chars
+            .as_str()
+            .split('.')
+            .map(|n| n.parse::<usize>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("cannot parse the output format `{}`.", format))?;
+        if !"DXB".contains(format_char) || widths.len() != 3 {
+            return Err(format!("cannot parse the output format `{}`.", format));
+        }
+        return Ok(OutputColumn {
+            name: name.to_string(),
+            variable,
+            format: format_char,
+            pad_left: widths[0],
+            len: widths[1],
+            pad_right: widths[2],
+        });
+    }
+
+    fn width(&self) -> usize {
+        return self.pad_left + self.len + self.pad_right;
+    }
+
+    /// The column name, centered.
+    fn header(&self) -> String {
+        let name: String = self.name.chars().take(self.width()).collect();
+        let left = (self.width() - name.len()) / 2;
+        let right = self.width() - name.len() - left;
+        return format!("{}{}{}", " ".repeat(left), name, " ".repeat(right));
+    }
+
+    /// The value, right-aligned in `len` characters between the paddings.
+    fn value(&self, cpu: &HackCpu) -> String {
+        let value = self.variable.get(cpu);
+        let text = match self.format {
+            'D' => (value as i16).to_string(),
+            'X' => format!("{:04X}", value),
+            _ => format!("{:016b}", value),
+        };
+        // too long values keep their last digits.
+        let text: String = if text.len() > self.len {
+            text[text.len() - self.len..].to_string()
+        } else {
+            format!("{:>width$}", text, width = self.len)
+        };
+        return format!(
+            "{}{}{}",
+            " ".repeat(self.pad_left),
+            text,
+            " ".repeat(self.pad_right)
+        );
+    }
+}
+
+/// The subset of the CPU emulator test script language used by the vm translator tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptCommand {
+    /// `load` or `load Prog.asm`, loads the translated program.
+    Load(Option<String>),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<OutputColumn>),
+    /// `set RAM[n] value`
+    Set(ScriptVariable, i16),
+    Repeat(u64, Vec<ScriptCommand>),
+    TickTock,
+    Output,
+    Echo(String),
+}
+
+struct Token {
+    text: String,
+    line: usize,
+}
+
+/// Splits a script in words, `{`, `}`, `,`, `;` and quoted strings, skipping comments.
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    let mut current = String::new();
+
+    let flush = |current: &mut String, tokens: &mut Vec<Token>, line: usize| {
+        if !current.is_empty() {
+            tokens.push(Token {
+                text: std::mem::take(current),
+                line,
+            });
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                flush(&mut current, &mut tokens, line);
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                flush(&mut current, &mut tokens, line);
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '"' => {
+                flush(&mut current, &mut tokens, line);
+                let string: String = chars.by_ref().take_while(|c| *c != '"').collect();
+                tokens.push(Token {
+                    text: format!("\"{}", string),
+                    line,
+                });
+            }
+            '{' | '}' | ',' | ';' => {
+                flush(&mut current, &mut tokens, line);
+                tokens.push(Token {
+                    text: c.to_string(),
+                    line,
+                });
+            }
+            c if c.is_whitespace() => {
+                flush(&mut current, &mut tokens, line);
+                if c == '\n' {
+                    line += 1;
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    flush(&mut current, &mut tokens, line);
+    return tokens;
+}
+
+/// Parses a CPU emulator test script (`.tst`).
+pub fn parse_script(text: &str) -> Result<Vec<ScriptCommand>, ScriptError> {
+    let tokens = tokenize(text);
+    let mut position = 0;
+    let commands = parse_block(&tokens, &mut position, false)?;
+    return Ok(commands);
+}
+
+fn parse_block(
+    tokens: &[Token],
+    position: &mut usize,
+    is_nested: bool,
+) -> Result<Vec<ScriptCommand>, ScriptError> {
+    let mut commands: Vec<ScriptCommand> = vec![];
+    loop {
+        let token = match tokens.get(*position) {
+            Some(token) => token,
+            None if is_nested => {
+                let line = tokens.last().map_or(1, |t| t.line);
+                return Err(ScriptError {
+                    line,
+                    message: "missing `}`.".to_string(),
+                });
+            }
+            None => return Ok(commands),
+        };
+        *position += 1;
+        match token.text.as_str() {
+            "," | ";" => continue,
+            "}" if is_nested => return Ok(commands),
+            _ => {}
+        }
+        let error = |message: String| ScriptError {
+            line: token.line,
+            message,
+        };
+
+        // arguments of the command, up to the next `,`, `;` or block delimiter.
+        let mut arguments: Vec<&str> = vec![];
+        while let Some(next) = tokens.get(*position) {
+            if [",", ";", "{", "}"].contains(&next.text.as_str()) {
+                break;
+            }
+            arguments.push(next.text.as_str());
+            *position += 1;
+        }
+        let argument = |n: usize| -> Result<&str, ScriptError> {
+            return match arguments.get(n) {
+                Some(argument) => Ok(argument),
+                None => Err(error(format!("`{}` expects an argument.", token.text))),
+            };
+        };
+
+        let command = match token.text.as_str() {
+            "load" => ScriptCommand::Load(arguments.first().map(|a| a.to_string())),
+            "output-file" => ScriptCommand::OutputFile(argument(0)?.to_string()),
+            "compare-to" => ScriptCommand::CompareTo(argument(0)?.to_string()),
+            "output-list" => {
+                let columns = arguments
+                    .iter()
+                    .map(|a| OutputColumn::parse(a))
+                    .collect::<Result<Vec<OutputColumn>, String>>()
+                    .map_err(error)?;
+                ScriptCommand::OutputList(columns)
+            }
+            "set" => {
+                let variable = match ScriptVariable::from_name(argument(0)?) {
+                    Some(variable) => variable,
+                    None => return Err(error(format!("unknown variable `{}`.", arguments[0]))),
+                };
+                let value = match argument(1)?.parse::<i16>() {
+                    Ok(value) => value,
+                    Err(_) => {
+                        return Err(error(format!("{} is not a 16-bit value.", arguments[1])))
+                    }
+                };
+                ScriptCommand::Set(variable, value)
+            }
+            "repeat" => {
+                let count = match argument(0)?.parse::<u64>() {
+                    Ok(count) => count,
+                    Err(_) => {
+                        return Err(error(format!("{} is not a repeat count.", arguments[0])))
+                    }
+                };
+                if tokens.get(*position).map(|t| t.text.as_str()) != Some("{") {
+                    return Err(error("`repeat` expects a `{` block.".to_string()));
+                }
+                *position += 1;
+                ScriptCommand::Repeat(count, parse_block(tokens, position, true)?)
+            }
+            "ticktock" => ScriptCommand::TickTock,
+            "output" => ScriptCommand::Output,
+            "echo" => ScriptCommand::Echo(argument(0)?.trim_start_matches('"').to_string()),
+            other => return Err(error(format!("unsupported command `{}`.", other))),
+        };
+        commands.push(command);
+    }
+}
+
+/// Output of a script run: the `.out` lines and the files named by the script.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptRun {
+    pub output: Vec<String>,
+    pub output_file: Option<String>,
+    pub compare_file: Option<String>,
+    pub echoes: Vec<String>,
+}
+
+/// Runs a parsed script. `load` gets the cpu to run from `loader`, called with the
+/// argument of the `load` command.
+pub fn run_script(
+    commands: &[ScriptCommand],
+    loader: &mut dyn FnMut(Option<&str>) -> Result<HackCpu, String>,
+) -> Result<ScriptRun, String> {
+    let mut run = ScriptRun::default();
+    let mut cpu: Option<HackCpu> = None;
+    let mut columns: Vec<OutputColumn> = vec![];
+    run_commands(commands, loader, &mut cpu, &mut columns, &mut run)?;
+    return Ok(run);
+}
+
+fn run_commands(
+    commands: &[ScriptCommand],
+    loader: &mut dyn FnMut(Option<&str>) -> Result<HackCpu, String>,
+    cpu: &mut Option<HackCpu>,
+    columns: &mut Vec<OutputColumn>,
+    run: &mut ScriptRun,
+) -> Result<(), String> {
+    for command in commands {
+        match command {
+            ScriptCommand::Load(name) => *cpu = Some(loader(name.as_deref())?),
+            ScriptCommand::OutputFile(name) => run.output_file = Some(name.clone()),
+            ScriptCommand::CompareTo(name) => run.compare_file = Some(name.clone()),
+            ScriptCommand::OutputList(list) => {
+                *columns = list.clone();
+                let headers: Vec<String> = columns.iter().map(|c| c.header()).collect();
+                run.output.push(format!("|{}|", headers.join("|")));
+            }
+            ScriptCommand::Echo(text) => run.echoes.push(text.clone()),
+            ScriptCommand::Repeat(count, body) => {
+                for _ in 0..*count {
+                    run_commands(body, loader, cpu, columns, run)?;
+                }
+            }
+            ScriptCommand::Set(_, _) | ScriptCommand::TickTock | ScriptCommand::Output => {
+                let cpu = match cpu {
+                    Some(cpu) => cpu,
+                    None => return Err("no program is loaded.".to_string()),
+                };
+                match command {
+                    ScriptCommand::Set(variable, value) => variable.set(cpu, *value as u16),
+                    ScriptCommand::TickTock => cpu.step().map_err(|e| e.to_string())?,
+                    _ => {
+                        let values: Vec<String> = columns.iter().map(|c| c.value(cpu)).collect();
+                        run.output.push(format!("|{}|", values.join("|")));
+                    }
+                }
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// A line of the output that differs from the compare file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompareFailure {
+    /// 1-based line number.
+    pub line: usize,
+    /// Empty if the output has more lines than the compare file.
+    pub expected: String,
+    /// Empty if the output has fewer lines than the compare file.
+    pub actual: String,
+}
+
+/// Compares the output lines with the compare file, ignoring whitespace like the CPU emulator.
+/// A missing or extra line is a failure.
+pub fn compare_output(output: &[String], compare_text: &str) -> Option<CompareFailure> {
+    let strip = |line: &str| -> String { line.chars().filter(|c| !c.is_whitespace()).collect() };
+    let expected_lines: Vec<&str> = compare_text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .collect();
+    for index in 0..output.len().max(expected_lines.len()) {
+        let expected = expected_lines.get(index).copied().unwrap_or("");
+        let actual = output.get(index).map_or("", |line| line.as_str());
+        if index >= output.len()
+            || index >= expected_lines.len()
+            || strip(expected) != strip(actual)
+        {
+            return Some(CompareFailure {
+                line: index + 1,
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            });
+        }
+    }
+    return None;
+}
@@ -15,7 +15,7 @@ pub fn write_to_file(file_pointer: &mut File, code_buffer_ref: &mut Vec<String>)
     code_buffer_ref.push("\n".to_string());
     let code_block_as_str = code_buffer_ref.join("\n");
     file_pointer
-        .write_all(&code_block_as_str.as_bytes())
+        .write_all(code_block_as_str.as_bytes())
         .unwrap();
     code_buffer_ref.clear();
 }
@@ -31,10 +31,10 @@ pub fn write_to_file(file_pointer: &mut File, code_buffer_ref: &mut Vec<String>)
 /// # Example:
 /// ```
 /// use std::path::Path;
-/// use crate::PathType;
+/// use vm_translator::{utils::create_output_path, PathType};
 /// let path = Path::new(".../test_directory");
 /// let output_path = create_output_path(&path, &PathType::Dir, true);
-/// // output_path: .../test_directory/test_directory.debug.asm
+/// assert_eq!(output_path, ".../test_directory/test_directory.debug.asm");
 /// ```
 ///
 /// [^note]: the function returns a string to the file, but does not create the output file.
@@ -54,12 +54,12 @@ pub fn create_output_path(path: &Path, path_type: &PathType, is_debug_option: bo
         PathType::Dir => {
             let mut file_name = format!(
                 "{}.asm",
-                path.to_path_buf().iter().last().unwrap().to_str().unwrap()
+                path.iter().next_back().unwrap().to_str().unwrap()
             );
             if is_debug_option {
                 file_name = format!(
                     "{}.debug.asm",
-                    path.to_path_buf().iter().last().unwrap().to_str().unwrap()
+                    path.iter().next_back().unwrap().to_str().unwrap()
                 );
             }
             let mut temp_path = path.to_path_buf();
@@ -70,7 +70,8 @@ pub fn create_output_path(path: &Path, path_type: &PathType, is_debug_option: bo
     return output_path;
 }
 
-/// Looks for all vm files in a given directory.
+/// Looks for all vm files in a given directory, sorted by path so that the
+/// output (and the order of reported errors) doesn't depend on the file system.
 ///
 /// Prints warning if argument is not a directory.
 pub fn search_vm_files(dir: &Path, files_vec_ref: &mut Vec<PathBuf>) {
@@ -81,6 +82,7 @@ pub fn search_vm_files(dir: &Path, files_vec_ref: &mut Vec<PathBuf>) {
                 files_vec_ref.push(path);
             }
         }
+        files_vec_ref.sort();
     } else {
         println!(
             "{}",
@@ -93,10 +95,11 @@ pub fn search_vm_files(dir: &Path, files_vec_ref: &mut Vec<PathBuf>) {
 ///
 /// # Example:
 /// ```
-/// let string_with_padding = add_padding(&String::from("hello"), 2);
+/// # use vm_translator::utils::add_padding;
+/// let string_with_padding = add_padding("hello", 2);
 /// assert_eq!("  hello", string_with_padding);
 /// ```
-pub fn add_padding(s: &String, n: usize) -> String {
+pub fn add_padding(s: &str, n: usize) -> String {
     let mut new_string: String = "".to_owned();
     for _ in 0..n {
         new_string.push(' ');
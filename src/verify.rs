@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::assembler::rom_size;
+use crate::code_generator::{CodeGenerator, SP};
+use crate::hack_cpu::{CpuError, HackCpu};
+use crate::parser::{ParsedCommand, VmFile};
+use crate::vm_interpreter::{VmInterpreter, VmRuntimeError};
+use crate::TranslateOptions;
+
+/// RAM cells the translated code uses as scratch: the last 3 registers of `temp`
+/// (used by `return`) and R13..R15. They are not compared.
+const SCRATCH_ADDRESSES: std::ops::RangeInclusive<usize> = 10..=15;
+
+/// Maximum number of instructions the translated code of a single vm command may take.
+const MAX_CYCLES_PER_COMMAND: u64 = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RamMismatch {
+    pub address: usize,
+    /// Value written by the reference interpreter.
+    pub expected: i16,
+    /// Value written by the translated code.
+    pub actual: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DivergenceKind {
+    /// Both sides reached the next command, with different RAM contents.
+    Ram(Vec<RamMismatch>),
+    /// The translated code didn't continue at the ROM address of the next vm command.
+    ControlFlow {
+        expected_rom: usize,
+        actual_rom: u16,
+    },
+}
+
+/// First vm command after which the interpreter and the translated code disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Number of vm commands executed before the diverging one.
+    pub step: u64,
+    /// File name and command, `None` for the bootstrap code.
+    pub command: Option<(String, ParsedCommand)>,
+    pub kind: DivergenceKind,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.command {
+            Some((file, parsed)) => writeln!(
+                f,
+                "[ERROR] divergence at step {}, {}:{}: `{}`",
+                self.step, file, parsed.span.line, parsed.command
+            )?,
+            None => writeln!(f, "[ERROR] divergence in the bootstrap code")?,
+        }
+        match &self.kind {
+            DivergenceKind::Ram(mismatches) => {
+                for mismatch in mismatches {
+                    writeln!(
+                        f,
+                        "    RAM[{}]: expected {}, translated code has {}",
+                        mismatch.address, mismatch.expected, mismatch.actual
+                    )?;
+                }
+            }
+            DivergenceKind::ControlFlow {
+                expected_rom,
+                actual_rom,
+            } => writeln!(
+                f,
+                "    expected to continue at ROM[{}], translated code is at ROM[{}]",
+                expected_rom, actual_rom
+            )?,
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    Cpu(CpuError),
+    Interpreter(VmRuntimeError),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Cpu(e) => write!(f, "{}", e),
+            VerifyError::Interpreter(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Result of a verification run without runtime errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Number of vm commands executed and compared.
+    pub steps: u64,
+    pub is_halted: bool,
+    pub divergence: Option<Divergence>,
+}
+
+/// Runs a vm program on the reference interpreter and its translation on the HACK cpu
+/// emulator in lockstep, comparing the RAM written by both after every vm command.
+///
+/// The return addresses saved by `call` are compared through the ROM address of the vm
+/// command they point to.
+pub struct Verifier {
+    pub interpreter: VmInterpreter,
+    pub cpu: HackCpu,
+    /// ROM address of the first instruction of each vm command; the last entry is the
+    /// end of the program.
+    rom_starts: Vec<usize>,
+    /// vm command starting at each ROM address (the first one if several commands,
+    /// e.g. labels, share an address).
+    commands_at: HashMap<usize, usize>,
+    is_bootstrap_done: bool,
+}
+
+impl Verifier {
+    /// Translates `files` with `options`. Tail calls (`is_tail_calls_option`) are reported
+    /// as divergences since the interpreter keeps the frame of every call.
+    pub fn new(files: &[VmFile], options: &TranslateOptions) -> Result<Verifier, VerifyError> {
+        let code_blocks = CodeGenerator::new(options).generate_code_blocks(files);
+
+        let mut rom_starts: Vec<usize> = vec![];
+        let mut rom_address: usize = 0;
+        for block in &code_blocks {
+            rom_address += rom_size(block).map_err(|e| VerifyError::Cpu(CpuError::Load(e)))?;
+            // the first block is the bootstrap, the others start at the end of the previous one.
+            rom_starts.push(rom_address);
+        }
+        let mut commands_at: HashMap<usize, usize> = HashMap::new();
+        for (pc, rom_start) in rom_starts.iter().enumerate() {
+            commands_at.entry(*rom_start).or_insert(pc);
+        }
+
+        let cpu = HackCpu::from_asm(&code_blocks.concat()).map_err(VerifyError::Cpu)?;
+        return Ok(Verifier {
+            interpreter: VmInterpreter::new(files, options),
+            cpu,
+            rom_starts,
+            commands_at,
+            is_bootstrap_done: false,
+        });
+    }
+
+    /// Sets a RAM cell on both sides.
+    pub fn set_ram_value(&mut self, address: usize, value: i16) {
+        self.interpreter.set_ram_value(address, value);
+        self.cpu.set_ram_value(address, value);
+    }
+
+    /// Executes and compares up to `max_steps` vm commands, stopping at the first divergence.
+    pub fn run(&mut self, max_steps: u64) -> Result<VerifyReport, VerifyError> {
+        let start = self.interpreter.steps;
+        let mut divergence: Option<Divergence> = None;
+
+        if !self.is_bootstrap_done {
+            self.is_bootstrap_done = true;
+            // the interpreter sets up the bootstrap when it is created.
+            let expected_writes = self.interpreter.last_writes().to_vec();
+            divergence = self.sync(None, expected_writes)?;
+        }
+
+        while divergence.is_none()
+            && self.interpreter.steps - start < max_steps
+            && !self.interpreter.is_halted()
+        {
+            let command = self
+                .interpreter
+                .current_command()
+                .map(|(file, parsed)| (file.to_string(), parsed.clone()));
+            self.interpreter.step().map_err(VerifyError::Interpreter)?;
+            let expected_writes = self.interpreter.last_writes().to_vec();
+            divergence = self.sync(command, expected_writes)?;
+        }
+
+        return Ok(VerifyReport {
+            steps: self.interpreter.steps - start,
+            is_halted: self.interpreter.is_halted(),
+            divergence,
+        });
+    }
+
+    /// Runs the cpu up to the ROM address of the interpreter's next command, then compares
+    /// the RAM cells written by either side.
+    fn sync(
+        &mut self,
+        command: Option<(String, ParsedCommand)>,
+        expected_writes: Vec<usize>,
+    ) -> Result<Option<Divergence>, VerifyError> {
+        let step = self.interpreter.steps.saturating_sub(1);
+        let target = self.rom_start(self.interpreter.pc);
+        let mut cpu_writes: Vec<usize> = vec![];
+        // cells the translated code wrote at or above its own SP (pushed temporaries).
+        let mut cpu_stack_writes: Vec<usize> = vec![];
+        let mut cycles: u64 = 0;
+
+        // past the end of the program, both sides have halted wherever the cpu stopped.
+        let is_end_of_program = target >= self.cpu.rom().len();
+        while !(usize::from(self.cpu.pc) == target || is_end_of_program && self.cpu.is_halted()) {
+            let pc = usize::from(self.cpu.pc);
+            let is_other_command = cycles > 0 && self.commands_at.contains_key(&pc);
+            if is_other_command || cycles >= MAX_CYCLES_PER_COMMAND || self.cpu.is_halted() {
+                return Ok(Some(Divergence {
+                    step,
+                    command,
+                    kind: DivergenceKind::ControlFlow {
+                        expected_rom: target,
+                        actual_rom: self.cpu.pc,
+                    },
+                }));
+            }
+            // C-instructions with `M` in their destination (d3 bit) write RAM[A].
+            let instruction = self.cpu.rom()[pc];
+            if instruction & 0x8000 != 0 && instruction & 0b1000 != 0 {
+                let address = usize::from(self.cpu.a);
+                if address >= usize::from(self.cpu.ram[SP]) {
+                    cpu_stack_writes.push(address);
+                }
+                cpu_writes.push(address);
+            }
+            self.cpu.step().map_err(VerifyError::Cpu)?;
+            cycles += 1;
+        }
+
+        // temporaries the translated code left above the final SP are dead, unless the
+        // interpreter wrote the same cell.
+        let sp = usize::from(self.interpreter.ram[SP]);
+        let mut written = expected_writes.clone();
+        written.extend(cpu_writes.into_iter().filter(|address| {
+            *address < sp
+                || !cpu_stack_writes.contains(address)
+                || expected_writes.contains(address)
+        }));
+        written.sort_unstable();
+        written.dedup();
+        let mismatches: Vec<RamMismatch> = written
+            .into_iter()
+            .filter(|address| !SCRATCH_ADDRESSES.contains(address))
+            .filter_map(|address| {
+                let expected = self.expected_cpu_value(address);
+                let actual = self.cpu.ram_value(address);
+                if expected == actual {
+                    return None;
+                }
+                return Some(RamMismatch {
+                    address,
+                    expected,
+                    actual,
+                });
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            return Ok(None);
+        }
+        return Ok(Some(Divergence {
+            step,
+            command,
+            kind: DivergenceKind::Ram(mismatches),
+        }));
+    }
+
+    /// ROM address of the vm command `pc` (the end of the program if `pc` is past the end).
+    fn rom_start(&self, pc: usize) -> usize {
+        // rom_starts[0] is the end of the bootstrap, i.e. the start of the first command.
+        return match self.rom_starts.get(pc) {
+            Some(rom_start) => *rom_start,
+            None => self.cpu.rom().len(),
+        };
+    }
+
+    /// Value the translated code should hold at `address`, translating saved return
+    /// addresses from vm commands to ROM addresses.
+    fn expected_cpu_value(&self, address: usize) -> i16 {
+        let value = self.interpreter.ram_value(address);
+        let is_return_address = self
+            .interpreter
+            .frames
+            .iter()
+            .any(|frame| frame.return_address_cell == address);
+        if is_return_address {
+            return self.rom_start(value as u16 as usize) as i16;
+        }
+        return value;
+    }
+}
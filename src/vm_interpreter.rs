@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::code_generator::{BootstrapMode, ARG, LCL, SP, THAT, THIS};
+use crate::hack_cpu::RAM_SIZE;
+use crate::parser::{ArithmeticOp, ParsedCommand, Segment, VmCommand, VmFile};
+use crate::TranslateOptions;
+
+/// First RAM address of the `temp` segment (RAM[5..12]).
+const TEMP_BASE_ADDRESS: usize = 5;
+
+/// First RAM address given to static variables, in the order the assembler allocates them.
+const STATIC_BASE_ADDRESS: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmRuntimeError {
+    /// Name of the vm file, including its extension.
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for VmRuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "[ERROR] {}:{}: {}", self.file, self.line, self.message);
+    }
+}
+
+impl std::error::Error for VmRuntimeError {}
+
+/// A command of the flattened program.
+struct Instruction {
+    file_index: usize,
+    /// Index of the `function` command (or first command of the file) that starts
+    /// the scope of the labels used by this command. 0 for every command if the labels
+    /// are global (`TranslateOptions::is_global_labels_option`).
+    scope: usize,
+    parsed: ParsedCommand,
+}
+
+/// A frame pushed by `call`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub function_name: String,
+    /// RAM address of the saved return address (the first word of the frame).
+    pub return_address_cell: usize,
+}
+
+/// Reference interpreter that executes vm commands directly, using the memory layout
+/// of the translated code (segment pointers in RAM[0..4], temp in RAM[5..12], statics
+/// from RAM[16], stack from the SP base address).
+///
+/// Return addresses saved in a frame are indices of vm commands (see `pc`), not ROM addresses.
+///
+/// # Example:
+/// ```
+/// use vm_translator::{parse_program, vm_interpreter::VmInterpreter, TranslateOptions, VmSource};
+///
+/// let sources = vec![VmSource::new("Main", "push constant 7\npush constant 8\nadd\n")];
+/// let options = TranslateOptions::default();
+/// let program = parse_program(&sources, &options).unwrap();
+/// let mut interpreter = VmInterpreter::new(&program.files, &options);
+/// interpreter.set_ram_value(0, 256);
+/// interpreter.run(100).unwrap();
+/// assert_eq!(interpreter.ram_value(256), 15);
+/// ```
+pub struct VmInterpreter {
+    file_names: Vec<String>,
+    program: Vec<Instruction>,
+    labels: HashMap<(usize, String), usize>,
+    functions: HashMap<String, usize>,
+    static_addresses: HashMap<(usize, u16), usize>,
+    pub ram: Vec<u16>,
+    /// Index of the next command to execute in the flattened program (all files in order).
+    pub pc: usize,
+    pub frames: Vec<Frame>,
+    /// Number of commands executed.
+    pub steps: u64,
+    /// RAM addresses written by the last step.
+    last_writes: Vec<usize>,
+}
+
+impl VmInterpreter {
+    /// Loads the files and runs the bootstrap selected by `options`.
+    pub fn new(files: &[VmFile], options: &TranslateOptions) -> VmInterpreter {
+        let mut program: Vec<Instruction> = vec![];
+        let mut labels: HashMap<(usize, String), usize> = HashMap::new();
+        let mut functions: HashMap<String, usize> = HashMap::new();
+        let mut static_addresses: HashMap<(usize, u16), usize> = HashMap::new();
+
+        for (file_index, file) in files.iter().enumerate() {
+            let mut scope = if options.is_global_labels_option {
+                0
+            } else {
+                program.len()
+            };
+            for parsed in &file.commands {
+                let pc = program.len();
+                match &parsed.command {
+                    VmCommand::Function { name, .. } => {
+                        if !options.is_global_labels_option {
+                            scope = pc;
+                        }
+                        functions.entry(name.clone()).or_insert(pc);
+                    }
+                    VmCommand::Label(label) => {
+                        labels.entry((scope, label.clone())).or_insert(pc);
+                    }
+                    _ => {}
+                }
+                for index in static_indices(&parsed.command) {
+                    let next_address = STATIC_BASE_ADDRESS + static_addresses.len();
+                    static_addresses
+                        .entry((file_index, index))
+                        .or_insert(next_address);
+                }
+                program.push(Instruction {
+                    file_index,
+                    scope,
+                    parsed: parsed.clone(),
+                });
+            }
+        }
+
+        let mut interpreter = VmInterpreter {
+            file_names: files.iter().map(|f| format!("{}.vm", f.name)).collect(),
+            program,
+            labels,
+            functions,
+            static_addresses,
+            ram: vec![0; RAM_SIZE],
+            pc: 0,
+            frames: vec![],
+            steps: 0,
+            last_writes: vec![],
+        };
+
+        let bootstrap = options
+            .bootstrap
+            .unwrap_or_else(|| BootstrapMode::detect(files));
+        let base_addresses = options.base_addresses;
+        match bootstrap {
+            BootstrapMode::None => {}
+            BootstrapMode::SpOnly => interpreter.ram[SP] = base_addresses.sp as u16,
+            BootstrapMode::Full => {
+                interpreter.ram[SP] = base_addresses.sp as u16;
+                interpreter.ram[LCL] = base_addresses.lcl as u16;
+                interpreter.ram[ARG] = base_addresses.arg as u16;
+                interpreter.ram[THIS] = base_addresses.this as u16;
+                interpreter.ram[THAT] = base_addresses.that as u16;
+                // like the translated bootstrap, returning from Sys.init falls through
+                // to the first command of the program.
+                if let Some(&target) = interpreter.functions.get("Sys.init") {
+                    interpreter.push_frame("Sys.init", 0, 0);
+                    interpreter.pc = target;
+                }
+            }
+        }
+        return interpreter;
+    }
+
+    /// Returns RAM[address] as a signed value.
+    pub fn ram_value(&self, address: usize) -> i16 {
+        return self.ram[address] as i16;
+    }
+
+    pub fn set_ram_value(&mut self, address: usize, value: i16) {
+        self.ram[address] = value as u16;
+    }
+
+    /// True once the last command of the program has been executed.
+    pub fn is_halted(&self) -> bool {
+        return self.pc >= self.program.len();
+    }
+
+    /// Returns the next command to execute and the name of its file.
+    pub fn current_command(&self) -> Option<(&str, &ParsedCommand)> {
+        return self.program.get(self.pc).map(|instruction| {
+            (
+                self.file_names[instruction.file_index].as_str(),
+                &instruction.parsed,
+            )
+        });
+    }
+
+    /// RAM address of static variable `index` of file `file_index`, if the program uses it.
+    pub fn static_address(&self, file_index: usize, index: u16) -> Option<usize> {
+        return self.static_addresses.get(&(file_index, index)).copied();
+    }
+
+    /// Number of static variables used by the program (RAM[16..16 + n]).
+    pub fn static_count(&self) -> usize {
+        return self.static_addresses.len();
+    }
+
+    /// RAM addresses written by the last step, in order (may contain duplicates).
+    pub fn last_writes(&self) -> &[usize] {
+        return &self.last_writes;
+    }
+
+    /// Executes up to `max_steps` commands, stopping early if the program halts.
+    ///
+    /// Returns the number of commands executed.
+    pub fn run(&mut self, max_steps: u64) -> Result<u64, VmRuntimeError> {
+        let start = self.steps;
+        while self.steps - start < max_steps && !self.is_halted() {
+            self.step()?;
+        }
+        return Ok(self.steps - start);
+    }
+
+    /// Executes a single command. Does nothing if the program is halted.
+    pub fn step(&mut self) -> Result<(), VmRuntimeError> {
+        let instruction = match self.program.get(self.pc) {
+            Some(instruction) => instruction,
+            None => return Ok(()),
+        };
+        let command = instruction.parsed.command.clone();
+        let file_index = instruction.file_index;
+        let scope = instruction.scope;
+        let mut next_pc = self.pc + 1;
+        self.last_writes.clear();
+
+        match &command {
+            VmCommand::Push { segment, index } => {
+                let value = if *segment == Segment::Constant {
+                    *index
+                } else {
+                    let address = self.segment_address(*segment, *index, file_index)?;
+                    self.ram[address]
+                };
+                self.push(value)?;
+            }
+            VmCommand::Pop { segment, index } => {
+                let value = self.pop()?;
+                let address = self.segment_address(*segment, *index, file_index)?;
+                self.write(address, value);
+            }
+            VmCommand::Move {
+                source,
+                source_index,
+                segment,
+                index,
+            } => {
+                let value = if *source == Segment::Constant {
+                    *source_index
+                } else {
+                    let address = self.segment_address(*source, *source_index, file_index)?;
+                    self.ram[address]
+                };
+                let address = self.segment_address(*segment, *index, file_index)?;
+                self.write(address, value);
+            }
+            VmCommand::Arithmetic(op) => {
+                let y = self.pop()?;
+                let result = if op.is_unary() {
+                    match op {
+                        ArithmeticOp::Neg => y.wrapping_neg(),
+                        _ => !y,
+                    }
+                } else {
+                    let x = self.pop()?;
+                    let boolean = |b: bool| if b { 0xFFFF } else { 0 };
+                    match op {
+                        ArithmeticOp::Add => x.wrapping_add(y),
+                        ArithmeticOp::Sub => x.wrapping_sub(y),
+                        ArithmeticOp::Eq => boolean(x == y),
+                        ArithmeticOp::Gt => boolean((x as i16) > (y as i16)),
+                        ArithmeticOp::Lt => boolean((x as i16) < (y as i16)),
+                        ArithmeticOp::And => x & y,
+                        _ => x | y,
+                    }
+                };
+                self.push(result)?;
+            }
+            VmCommand::Label(_) => {}
+            VmCommand::Goto(label) => next_pc = self.label_target(scope, label)?,
+            VmCommand::IfGoto(label) => {
+                if self.pop()? != 0 {
+                    next_pc = self.label_target(scope, label)?;
+                }
+            }
+            VmCommand::Function { n_vars, .. } => {
+                for _ in 0..*n_vars {
+                    self.push(0)?;
+                }
+            }
+            VmCommand::Call { name, n_args } => {
+                next_pc = match self.functions.get(name) {
+                    Some(&target) => target,
+                    None => return Err(self.error(&format!("function {} is not defined.", name))),
+                };
+                self.push_frame(name, *n_args, self.pc + 1);
+            }
+            VmCommand::Return => {
+                let frame = usize::from(self.ram[LCL]);
+                if frame < 5 {
+                    return Err(self.error("return outside of a call frame."));
+                }
+                self.checked_address(frame - 1)?; // the last word of the frame (saved THAT).
+                let return_address = usize::from(self.ram[frame - 5]);
+                let return_value = self.pop()?;
+                let arg = self.checked_address(usize::from(self.ram[ARG]))?;
+                self.write(arg, return_value);
+                self.write(SP, (arg + 1) as u16);
+                self.write(THAT, self.ram[frame - 1]);
+                self.write(THIS, self.ram[frame - 2]);
+                self.write(ARG, self.ram[frame - 3]);
+                self.write(LCL, self.ram[frame - 4]);
+                self.frames.pop();
+                next_pc = return_address;
+            }
+        }
+
+        self.pc = next_pc;
+        self.steps += 1;
+        return Ok(());
+    }
+
+    /// Pushes the frame of a call: return address, LCL, ARG, THIS and THAT.
+    fn push_frame(&mut self, function_name: &str, n_args: u16, return_address: usize) {
+        let sp = usize::from(self.ram[SP]);
+        self.frames.push(Frame {
+            function_name: function_name.to_string(),
+            return_address_cell: sp,
+        });
+        let saved = [
+            return_address as u16,
+            self.ram[LCL],
+            self.ram[ARG],
+            self.ram[THIS],
+            self.ram[THAT],
+        ];
+        for (offset, value) in saved.iter().enumerate() {
+            if sp + offset < RAM_SIZE {
+                self.write(sp + offset, *value);
+            }
+        }
+        self.write(SP, (sp + 5) as u16);
+        self.write(ARG, (sp + 5).wrapping_sub(usize::from(n_args) + 5) as u16);
+        self.write(LCL, (sp + 5) as u16);
+    }
+
+    fn write(&mut self, address: usize, value: u16) {
+        self.ram[address] = value;
+        self.last_writes.push(address);
+    }
+
+    fn push(&mut self, value: u16) -> Result<(), VmRuntimeError> {
+        let sp = usize::from(self.ram[SP]);
+        let address = self.checked_address(sp)?;
+        self.write(address, value);
+        self.write(SP, (sp + 1) as u16);
+        return Ok(());
+    }
+
+    fn pop(&mut self) -> Result<u16, VmRuntimeError> {
+        let sp = usize::from(self.ram[SP]);
+        if sp == 0 {
+            return Err(self.error("pop from an empty stack (SP = 0)."));
+        }
+        let address = self.checked_address(sp - 1)?;
+        self.write(SP, address as u16);
+        return Ok(self.ram[address]);
+    }
+
+    fn segment_address(
+        &self,
+        segment: Segment,
+        index: u16,
+        file_index: usize,
+    ) -> Result<usize, VmRuntimeError> {
+        let index = usize::from(index);
+        let address = match segment {
+            Segment::Local => usize::from(self.ram[LCL]) + index,
+            Segment::Argument => usize::from(self.ram[ARG]) + index,
+            Segment::This => usize::from(self.ram[THIS]) + index,
+            Segment::That => usize::from(self.ram[THAT]) + index,
+            Segment::Temp => TEMP_BASE_ADDRESS + index,
+            Segment::Pointer => THIS + index,
+            Segment::General => index,
+            Segment::Static => self.static_addresses[&(file_index, index as u16)],
+            Segment::Constant => unreachable!("constants have no address"),
+        };
+        return self.checked_address(address);
+    }
+
+    fn checked_address(&self, address: usize) -> Result<usize, VmRuntimeError> {
+        if address >= RAM_SIZE {
+            return Err(self.error(&format!(
+                "RAM[{}] is out of bounds (RAM holds {} words).",
+                address, RAM_SIZE
+            )));
+        }
+        return Ok(address);
+    }
+
+    fn label_target(&self, scope: usize, label: &str) -> Result<usize, VmRuntimeError> {
+        return match self.labels.get(&(scope, label.to_string())) {
+            Some(&target) => Ok(target),
+            None => Err(self.error(&format!("label {} is not declared.", label))),
+        };
+    }
+
+    fn error(&self, message: &str) -> VmRuntimeError {
+        let instruction = &self.program[self.pc];
+        return VmRuntimeError {
+            file: self.file_names[instruction.file_index].clone(),
+            line: instruction.parsed.span.line,
+            message: message.to_string(),
+        };
+    }
+}
+
+/// Indices of the static variables a command accesses, in the order it accesses them.
+fn static_indices(command: &VmCommand) -> Vec<u16> {
+    return match command {
+        VmCommand::Push {
+            segment: Segment::Static,
+            index,
+        }
+        | VmCommand::Pop {
+            segment: Segment::Static,
+            index,
+        } => vec![*index],
+        VmCommand::Move {
+            source,
+            source_index,
+            segment,
+            index,
+        } => [(source, source_index), (segment, index)]
+            .into_iter()
+            .filter(|(segment, _)| **segment == Segment::Static)
+            .map(|(_, index)| *index)
+            .collect(),
+        _ => vec![],
+    };
+}
@@ -0,0 +1,471 @@
+use std::collections::HashMap;
+
+use crate::analysis::{function_scopes, is_allowed, CallGraph};
+use crate::code_generator::BootstrapMode;
+use crate::parser::{ArithmeticOp, ParsedCommand, Segment, Span, VmCommand, VmFile};
+use crate::TranslateOptions;
+
+/// Largest value `push constant` accepts.
+const MAX_CONSTANT: u16 = 32767;
+
+/// Functions omitted by `eliminate_dead_functions`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeadFunctionReport {
+    /// Number of functions defined by the program.
+    pub function_count: usize,
+    /// Omitted functions in program order, with the number of vm commands of each.
+    pub dropped: Vec<(String, usize)>,
+}
+
+/// Omits the functions that can't be called from the entry points of the program:
+/// `Sys.init` when the full bootstrap calls it, functions called by commands that come
+/// before the first `function` of a file, and the functions matching
+/// `TranslateOptions::kept_functions` (`Class.function` or `Class.*`).
+///
+/// A program without entry point (no full bootstrap and no top-level call) keeps every
+/// function, since its functions are reached in some other way.
+pub fn eliminate_dead_functions(
+    files: &[VmFile],
+    options: &TranslateOptions,
+) -> (Vec<VmFile>, DeadFunctionReport) {
+    let call_graph = CallGraph::build(files);
+    let mut roots: Vec<&str> = call_graph
+        .top_level_calls
+        .iter()
+        .map(|call| call.callee.as_str())
+        .collect();
+    let bootstrap = options
+        .bootstrap
+        .unwrap_or_else(|| BootstrapMode::detect(files));
+    if bootstrap == BootstrapMode::Full {
+        roots.push("Sys.init");
+    }
+    let has_entry_point = !roots.is_empty();
+    roots.extend(
+        call_graph
+            .functions
+            .keys()
+            .filter(|name| !has_entry_point || is_allowed(name, &options.kept_functions))
+            .map(|name| name.as_str()),
+    );
+    let reachable = call_graph.reachable_from(&roots);
+
+    let mut report = DeadFunctionReport::default();
+    let mut live_files: Vec<VmFile> = vec![];
+    for file in files {
+        let mut commands: Vec<ParsedCommand> = vec![];
+        for scope in function_scopes(&file.commands) {
+            match scope.function_name {
+                Some(name) if !reachable.contains(name) => {
+                    report.function_count += 1;
+                    report
+                        .dropped
+                        .push((name.to_string(), scope.commands.len()));
+                }
+                function_name => {
+                    if function_name.is_some() {
+                        report.function_count += 1;
+                    }
+                    commands.extend_from_slice(scope.commands);
+                }
+            }
+        }
+        live_files.push(VmFile {
+            name: file.name.clone(),
+            commands,
+        });
+    }
+    return (live_files, report);
+}
+
+/// Number of cells of the `temp` segment.
+const TEMP_SIZE: u16 = 8;
+
+/// A function whose calls `inline_functions` may replace with its body.
+struct InlineCandidate<'a> {
+    file_index: usize,
+    n_vars: u16,
+    /// Commands between `function` and the final `return`.
+    body: &'a [ParsedCommand],
+}
+
+/// Replaces calls to small leaf functions with their body (`TranslateOptions::inline_threshold`).
+///
+/// A function is inlined if its body has at most `max_size` commands, made of `push`, `pop`
+/// and arithmetic only, followed by a single `return` with exactly the return value on the
+/// stack. Its arguments and locals are held in `temp` cells that the program never uses,
+/// and the pointers it sets are saved and restored in the same way. Calls are left as they
+/// are when there are not enough free `temp` cells, or when the function uses `static`
+/// from another file.
+pub fn inline_functions(files: &[VmFile], max_size: usize) -> Vec<VmFile> {
+    let used_temps: Vec<u16> = files
+        .iter()
+        .flat_map(|file| &file.commands)
+        .flat_map(|parsed| accessed_variables(&parsed.command))
+        .filter(|(segment, _)| *segment == Segment::Temp)
+        .map(|(_, index)| index)
+        .collect();
+    let free_temps: Vec<u16> = (0..TEMP_SIZE)
+        .filter(|index| !used_temps.contains(index))
+        .collect();
+
+    let mut candidates: HashMap<&str, InlineCandidate> = HashMap::new();
+    for (file_index, file) in files.iter().enumerate() {
+        for scope in function_scopes(&file.commands) {
+            let (Some(name), VmCommand::Function { n_vars, .. }) =
+                (scope.function_name, &scope.commands[0].command)
+            else {
+                continue;
+            };
+            if let Some(body) = inline_body(scope.commands, max_size) {
+                candidates.entry(name).or_insert(InlineCandidate {
+                    file_index,
+                    n_vars: *n_vars,
+                    body,
+                });
+            }
+        }
+    }
+
+    let mut inlined_files: Vec<VmFile> = vec![];
+    for (file_index, file) in files.iter().enumerate() {
+        let mut commands: Vec<ParsedCommand> = vec![];
+        for parsed in &file.commands {
+            let expansion = match &parsed.command {
+                VmCommand::Call { name, n_args } => candidates
+                    .get(name.as_str())
+                    .and_then(|c| expand_call(c, *n_args, file_index, parsed.span, &free_temps)),
+                _ => None,
+            };
+            match expansion {
+                Some(mut expansion) => commands.append(&mut expansion),
+                None => commands.push(parsed.clone()),
+            }
+        }
+        inlined_files.push(VmFile {
+            name: file.name.clone(),
+            commands,
+        });
+    }
+    return inlined_files;
+}
+
+/// Returns the body of a function (`function` command included in `commands`) if it can be
+/// inlined: see `inline_functions`.
+fn inline_body(commands: &[ParsedCommand], max_size: usize) -> Option<&[ParsedCommand]> {
+    let (last, rest) = commands.split_last()?;
+    if last.command != VmCommand::Return || rest.len() < 2 || rest.len() - 1 > max_size {
+        return None;
+    }
+    let body = &rest[1..];
+    let mut depth: usize = 0;
+    for parsed in body {
+        depth = match &parsed.command {
+            VmCommand::Push { .. } => depth + 1,
+            VmCommand::Pop { .. } => depth.checked_sub(1)?,
+            VmCommand::Arithmetic(op) if op.is_unary() => depth.checked_sub(1)? + 1,
+            VmCommand::Arithmetic(_) => depth.checked_sub(2)? + 1,
+            VmCommand::Move { .. } => depth,
+            _ => return None,
+        };
+    }
+    if depth != 1 {
+        return None;
+    }
+    return Some(body);
+}
+
+/// Body of `candidate` for a call with `n_args` arguments made from `file_index`, or
+/// `None` if this call can't be inlined.
+fn expand_call(
+    candidate: &InlineCandidate,
+    n_args: u16,
+    file_index: usize,
+    span: Span,
+    free_temps: &[u16],
+) -> Option<Vec<ParsedCommand>> {
+    let accessed: Vec<(Segment, u16)> = candidate
+        .body
+        .iter()
+        .flat_map(|parsed| accessed_variables(&parsed.command))
+        .collect();
+    let is_valid_access = |(segment, index): &(Segment, u16)| match segment {
+        Segment::Argument => *index < n_args,
+        Segment::Local => *index < candidate.n_vars,
+        Segment::Static => file_index == candidate.file_index,
+        _ => true,
+    };
+    if !accessed.iter().all(is_valid_access) {
+        return None;
+    }
+    let set_pointers: Vec<u16> = (0..2)
+        .filter(|index| {
+            candidate.body.iter().any(|parsed| {
+                matches!(
+                    parsed.command,
+                    VmCommand::Pop { segment: Segment::Pointer, index: i }
+                        | VmCommand::Move { segment: Segment::Pointer, index: i, .. } if i == *index
+                )
+            })
+        })
+        .collect();
+
+    // free_temps[..n_args] hold the arguments, then the locals, then the saved pointers.
+    let n_variables = usize::from(n_args) + usize::from(candidate.n_vars);
+    if n_variables + set_pointers.len() > free_temps.len() {
+        return None;
+    }
+    let remap = |segment: Segment, index: u16| match segment {
+        Segment::Argument => (Segment::Temp, free_temps[usize::from(index)]),
+        Segment::Local => (
+            Segment::Temp,
+            free_temps[usize::from(n_args) + usize::from(index)],
+        ),
+        _ => (segment, index),
+    };
+    let command = |command: VmCommand| ParsedCommand { command, span };
+    let push = |segment: Segment, index: u16| command(VmCommand::Push { segment, index });
+    let pop = |segment: Segment, index: u16| command(VmCommand::Pop { segment, index });
+
+    let mut expansion: Vec<ParsedCommand> = vec![];
+    for index in (0..n_args).rev() {
+        expansion.push(pop(Segment::Temp, free_temps[usize::from(index)]));
+    }
+    for index in 0..candidate.n_vars {
+        let (_, temp) = remap(Segment::Local, index);
+        expansion.push(push(Segment::Constant, 0));
+        expansion.push(pop(Segment::Temp, temp));
+    }
+    for (i, pointer) in set_pointers.iter().enumerate() {
+        expansion.push(push(Segment::Pointer, *pointer));
+        expansion.push(pop(Segment::Temp, free_temps[n_variables + i]));
+    }
+    for parsed in candidate.body {
+        expansion.push(command(match &parsed.command {
+            VmCommand::Push { segment, index } => {
+                let (segment, index) = remap(*segment, *index);
+                VmCommand::Push { segment, index }
+            }
+            VmCommand::Pop { segment, index } => {
+                let (segment, index) = remap(*segment, *index);
+                VmCommand::Pop { segment, index }
+            }
+            VmCommand::Move {
+                source,
+                source_index,
+                segment,
+                index,
+            } => {
+                let (source, source_index) = remap(*source, *source_index);
+                let (segment, index) = remap(*segment, *index);
+                VmCommand::Move {
+                    source,
+                    source_index,
+                    segment,
+                    index,
+                }
+            }
+            other => other.clone(),
+        }));
+    }
+    for (i, pointer) in set_pointers.iter().enumerate() {
+        expansion.push(push(Segment::Temp, free_temps[n_variables + i]));
+        expansion.push(pop(Segment::Pointer, *pointer));
+    }
+    return Some(expansion);
+}
+
+/// Segments and indices a command reads or writes, constants excluded.
+fn accessed_variables(command: &VmCommand) -> Vec<(Segment, u16)> {
+    let variables = match command {
+        VmCommand::Push { segment, index } | VmCommand::Pop { segment, index } => {
+            vec![(*segment, *index)]
+        }
+        VmCommand::Move {
+            source,
+            source_index,
+            segment,
+            index,
+        } => vec![(*source, *source_index), (*segment, *index)],
+        _ => vec![],
+    };
+    return variables
+        .into_iter()
+        .filter(|(segment, _)| *segment != Segment::Constant)
+        .collect();
+}
+
+/// Optimizes the vm commands of every file before translation (`-O2`). See `optimize_commands`.
+pub fn optimize_files(files: &[VmFile]) -> Vec<VmFile> {
+    return files
+        .iter()
+        .map(|file| VmFile {
+            name: file.name.clone(),
+            commands: optimize_commands(&file.commands),
+        })
+        .collect();
+}
+
+/// Rewrites a vm command stream into an equivalent, shorter one:
+///
+/// - arithmetic on constants is folded with 16-bit wraparound, e.g. `push constant 3`,
+///   `push constant 4`, `add` becomes `push constant 7`. `gt` and `lt` are signed
+///   comparisons, like in the generated code. Results above 32767 are written
+///   `push constant !x`, `not`;
+/// - `pop temp i`, `push temp i` is removed when temp i is popped again before being read;
+/// - `push x i`, `pop x i` is removed;
+/// - other `push`, `pop` pairs become a single `VmCommand::Move`.
+///
+/// Only adjacent commands are combined, so nothing is moved across a label. Folded and
+/// fused commands keep the position of their first command.
+pub fn optimize_commands(commands: &[ParsedCommand]) -> Vec<ParsedCommand> {
+    let mut optimized: Vec<ParsedCommand> = vec![];
+    for (i, parsed) in commands.iter().enumerate() {
+        match &parsed.command {
+            VmCommand::Arithmetic(op) => {
+                if !fold_constants(&mut optimized, *op) {
+                    optimized.push(parsed.clone());
+                }
+            }
+            VmCommand::Push {
+                segment: Segment::Temp,
+                index,
+            } if is_last_pop(&optimized, Segment::Temp, *index)
+                && is_temp_overwritten(&commands[i + 1..], *index) =>
+            {
+                optimized.pop(); // the value is still on the stack.
+            }
+            VmCommand::Pop { segment, index } => match optimized.last().map(|last| &last.command) {
+                Some(VmCommand::Push {
+                    segment: source,
+                    index: source_index,
+                }) => {
+                    let (source, source_index) = (*source, *source_index);
+                    let first = optimized.pop().unwrap();
+                    if (source, source_index) != (*segment, *index) {
+                        optimized.push(ParsedCommand {
+                            command: VmCommand::Move {
+                                source,
+                                source_index,
+                                segment: *segment,
+                                index: *index,
+                            },
+                            span: first.span,
+                        });
+                    }
+                }
+                _ => optimized.push(parsed.clone()),
+            },
+            _ => optimized.push(parsed.clone()),
+        }
+    }
+    return optimized;
+}
+
+/// Value of the constant pushed by the last commands: `push constant c` optionally followed
+/// by `neg` or `not`. Returns the value and the number of commands that push it.
+fn constant_at_end(commands: &[ParsedCommand]) -> Option<(u16, usize)> {
+    let pushed = |parsed: &ParsedCommand| match parsed.command {
+        VmCommand::Push {
+            segment: Segment::Constant,
+            index,
+        } => Some(index),
+        _ => None,
+    };
+    let (last, rest) = commands.split_last()?;
+    if let Some(value) = pushed(last) {
+        return Some((value, 1));
+    }
+    let value = pushed(rest.last()?)?;
+    return match last.command {
+        VmCommand::Arithmetic(ArithmeticOp::Neg) => Some((value.wrapping_neg(), 2)),
+        VmCommand::Arithmetic(ArithmeticOp::Not) => Some((!value, 2)),
+        _ => None,
+    };
+}
+
+/// Replaces the constants on top of `optimized` with the result of `op` applied to them.
+/// Returns false, leaving `optimized` untouched, if `op` can't be folded.
+fn fold_constants(optimized: &mut Vec<ParsedCommand>, op: ArithmeticOp) -> bool {
+    let Some((y, y_len)) = constant_at_end(optimized) else {
+        return false;
+    };
+    let boolean = |b: bool| if b { 0xFFFF } else { 0 };
+    let (result, len) = if op.is_unary() {
+        let result = if op == ArithmeticOp::Neg {
+            y.wrapping_neg()
+        } else {
+            !y
+        };
+        // `push constant c`, `neg` is already as short as it gets.
+        if y_len == 1 && result > MAX_CONSTANT {
+            return false;
+        }
+        (result, y_len)
+    } else {
+        let Some((x, x_len)) = constant_at_end(&optimized[..optimized.len() - y_len]) else {
+            return false;
+        };
+        let result = match op {
+            ArithmeticOp::Add => x.wrapping_add(y),
+            ArithmeticOp::Sub => x.wrapping_sub(y),
+            ArithmeticOp::Eq => boolean(x == y),
+            ArithmeticOp::Gt => boolean((x as i16) > (y as i16)),
+            ArithmeticOp::Lt => boolean((x as i16) < (y as i16)),
+            ArithmeticOp::And => x & y,
+            _ => x | y,
+        };
+        (result, x_len + y_len)
+    };
+
+    let span = optimized[optimized.len() - len].span;
+    optimized.truncate(optimized.len() - len);
+    let push = |value: u16| ParsedCommand {
+        command: VmCommand::Push {
+            segment: Segment::Constant,
+            index: value,
+        },
+        span,
+    };
+    if result <= MAX_CONSTANT {
+        optimized.push(push(result));
+    } else {
+        optimized.push(push(!result));
+        optimized.push(ParsedCommand {
+            command: VmCommand::Arithmetic(ArithmeticOp::Not),
+            span,
+        });
+    }
+    return true;
+}
+
+fn is_last_pop(optimized: &[ParsedCommand], segment: Segment, index: u16) -> bool {
+    return matches!(
+        optimized.last().map(|last| &last.command),
+        Some(VmCommand::Pop { segment: s, index: i }) if *s == segment && *i == index
+    );
+}
+
+/// True if temp `index` is popped again before being read, within straight-line code.
+/// Calls, returns and jumps end the search since temp is shared by every function.
+fn is_temp_overwritten(commands: &[ParsedCommand], index: u16) -> bool {
+    let is_temp = |segment: &Segment, i: &u16| *segment == Segment::Temp && *i == index;
+    for parsed in commands {
+        match &parsed.command {
+            VmCommand::Push { segment, index } if is_temp(segment, index) => return false,
+            VmCommand::Pop { segment, index } if is_temp(segment, index) => return true,
+            VmCommand::Move {
+                source,
+                source_index,
+                ..
+            } if is_temp(source, source_index) => return false,
+            VmCommand::Move { segment, index, .. } if is_temp(segment, index) => return true,
+            VmCommand::Push { .. }
+            | VmCommand::Pop { .. }
+            | VmCommand::Move { .. }
+            | VmCommand::Arithmetic(_) => {}
+            _ => return false,
+        }
+    }
+    return false;
+}
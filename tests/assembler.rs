@@ -0,0 +1,107 @@
+#![allow(clippy::needless_return)]
+use vm_translator::assembler::{
+    assemble, assemble_lines, parse_asm, parse_hack_text, parse_instruction, AsmError, Instruction,
+};
+
+fn parsed(line: &str) -> Instruction {
+    return parse_instruction(line).unwrap().unwrap();
+}
+
+#[test]
+fn comp_and_dest_are_canonicalized() {
+    assert_eq!(parsed("MD = M & D"), Instruction::c("MD", "D&M", ""));
+    assert_eq!(parsed("DM=D&M"), Instruction::c("MD", "D&M", ""));
+    assert_eq!(parsed("AMD = 1 + D"), Instruction::c("AMD", "D+1", ""));
+    assert_eq!(parsed("M = M | D"), Instruction::c("M", "D|M", ""));
+    assert_eq!(parsed("D = M - D"), Instruction::c("D", "M-D", ""));
+    assert_eq!(parsed("D; JGT // comment"), Instruction::c("", "D", "JGT"));
+    assert_eq!(parsed("MD = M & D").to_string(), "MD=D&M");
+
+    assert_eq!(parse_instruction("   // only a comment"), Ok(None));
+    assert_eq!(
+        parse_instruction("D = D - 1 - A"),
+        Err("D-1-A is not a valid computation.".to_string())
+    );
+    assert_eq!(
+        parse_instruction("X = A"),
+        Err("X is not a valid destination.".to_string())
+    );
+}
+
+#[test]
+fn instructions_are_encoded() {
+    let words = assemble_lines(&["@21", "D = D + M", "AM = M - 1", "M = M | D", "0; JMP"]);
+    assert_eq!(
+        words,
+        Ok(vec![
+            21,
+            0b1111_0000_1001_0000,
+            0b1111_1100_1010_1000,
+            0b1111_0101_0100_1000,
+            0b1110_1010_1000_0111,
+        ])
+    );
+}
+
+#[test]
+fn labels_and_variables_are_resolved() {
+    let lines = [
+        "@i",      // 0: first variable, RAM[16]
+        "M = 1",   // 1
+        "(LOOP)",  // label of ROM[2]
+        "@j",      // 2: second variable, RAM[17]
+        "@i",      // 3
+        "@END",    // 4: label defined later
+        "0; JMP",  // 5
+        "@LOOP",   // 6
+        "(END)",   // label of ROM[7]
+        "@R13",    // 7
+        "@SCREEN", // 8
+        "@THAT",   // 9
+        "@END",    // 10
+    ];
+    let words = assemble_lines(&lines).unwrap();
+    assert_eq!(words[0], 16);
+    assert_eq!(words[2], 17);
+    assert_eq!(words[3], 16);
+    assert_eq!(words[4], 7);
+    assert_eq!(words[6], 2);
+    assert_eq!(words[7..], [13, 16384, 4, 7]);
+}
+
+#[test]
+fn errors_report_the_source_line() {
+    // debug output: an entry may hold several lines.
+    let lines = ["\n// push constant 1", "@1", "D = A", "\n// bad", "D = Q"];
+    assert_eq!(
+        parse_asm(&lines),
+        Err(AsmError {
+            line: 7,
+            message: "Q is not a valid computation.".to_string(),
+        })
+    );
+
+    // a label past the reach of an A-instruction, reported at the instruction using it.
+    let mut lines: Vec<String> = vec!["// far away".to_string(), "@END".to_string()];
+    lines.extend(std::iter::repeat_n("D = 0".to_string(), 32767));
+    lines.push("(END)".to_string());
+    lines.push("\n@END".to_string());
+    let error = assemble_lines(&lines).unwrap_err();
+    assert_eq!(error.line, 2);
+    assert_eq!(
+        error.message,
+        "label END is at ROM address 32768, out of the reach of an A-instruction."
+    );
+
+    // without a source, instructions count as one line each.
+    let instructions = parse_asm(&lines).unwrap();
+    assert_eq!(assemble(&instructions).unwrap_err().line, 1);
+
+    assert_eq!(
+        parse_hack_text("0000000000000001\n\n101\n").unwrap_err(),
+        AsmError {
+            line: 3,
+            message: "101 is not a 16-bit binary word.".to_string(),
+        }
+    );
+}
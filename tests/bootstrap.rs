@@ -0,0 +1,39 @@
+use std::{env, fs, path::PathBuf};
+use vm_translator::{translate, utils::search_vm_files, TranslateOptions, VmSource};
+
+#[test]
+fn bootstrap_is_emitted_once_for_a_multi_file_directory() {
+    let dir = env::temp_dir().join(format!("vm_translator_bootstrap_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for (name, text) in [
+        ("Sys", "function Sys.init 0\ncall Main.main 0\nlabel END\ngoto END\n"),
+        ("Main", "function Main.main 0\npush constant 1\ncall Foo.bar 1\nreturn\n"),
+        ("Foo", "function Foo.bar 0\npush argument 0\nreturn\n"),
+    ] {
+        fs::write(dir.join(format!("{}.vm", name)), text).unwrap();
+    }
+
+    let mut files_vec: Vec<PathBuf> = vec![];
+    search_vm_files(&dir, &mut files_vec);
+    let sources: Vec<VmSource> = files_vec
+        .iter()
+        .map(|p| VmSource::from_path(p).unwrap())
+        .collect();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(sources.len(), 3);
+
+    let program = translate(&sources, &TranslateOptions::default()).unwrap();
+
+    // the stack pointer is initialized once, before any user code.
+    assert_eq!(program.lines[..4], ["@256", "D = A", "@0", "M = D"]);
+    let sp_setups = program.lines.iter().filter(|l| *l == "@256").count();
+    assert_eq!(sp_setups, 1);
+
+    // Sys.init is called from the bootstrap only.
+    let sys_init_calls = program
+        .lines
+        .iter()
+        .filter(|l| l.starts_with("(Sys.init_ret_"))
+        .count();
+    assert_eq!(sys_init_calls, 1);
+}
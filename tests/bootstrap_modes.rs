@@ -0,0 +1,140 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::{BaseAddresses, BootstrapMode},
+    config::apply_config,
+    hack_cpu::HackCpu,
+    parse_program, translate, TranslateOptions, VmSource,
+};
+
+const SYS: &str = "function Sys.init 0
+push pointer 0
+pop static 0
+push pointer 1
+pop static 1
+label END
+goto END
+";
+
+const NO_SYS: &str = "push constant 7
+pop static 0
+";
+
+fn run(sources: &[VmSource], options: &TranslateOptions) -> HackCpu {
+    let program = translate(sources, options).unwrap();
+    let mut cpu = HackCpu::from_asm(&program.lines).unwrap();
+    cpu.run(1000).unwrap();
+    return cpu;
+}
+
+#[test]
+fn the_bootstrap_mode_is_detected_from_sys_init() {
+    let options = TranslateOptions::default();
+    let with_sys = parse_program(&[VmSource::new("Sys", SYS)], &options).unwrap();
+    assert_eq!(BootstrapMode::detect(&with_sys.files), BootstrapMode::Full);
+    let without_sys = parse_program(&[VmSource::new("Main", NO_SYS)], &options).unwrap();
+    assert_eq!(
+        BootstrapMode::detect(&without_sys.files),
+        BootstrapMode::None
+    );
+
+    // no bootstrap: the first instruction is the first vm command.
+    let program = translate(&[VmSource::new("Main", NO_SYS)], &options).unwrap();
+    assert_eq!(program.lines[..2], ["@7", "D = A"]);
+}
+
+#[test]
+fn the_full_bootstrap_sets_every_segment_then_calls_sys_init() {
+    let options = TranslateOptions {
+        base_addresses: BaseAddresses {
+            sp: 300,
+            lcl: 400,
+            arg: 500,
+            this: 3000,
+            that: 4000,
+        },
+        ..Default::default()
+    };
+    let cpu = run(&[VmSource::new("Sys", SYS)], &options);
+
+    // the frame of Sys.init is saved at the custom stack base.
+    assert_eq!(cpu.ram_value(0), 305);
+    assert_eq!(cpu.ram_value(1), 305);
+    assert_eq!(cpu.ram_value(2), 300);
+    assert_eq!(cpu.ram[301..305], [400, 500, 3000, 4000]);
+    assert_eq!(cpu.ram_value(16), 3000);
+    assert_eq!(cpu.ram_value(17), 4000);
+}
+
+#[test]
+fn sp_only_sets_the_stack_pointer_and_calls_nothing() {
+    let options = TranslateOptions {
+        bootstrap: Some(BootstrapMode::SpOnly),
+        base_addresses: BaseAddresses {
+            sp: 300,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let program = translate(&[VmSource::new("Main", NO_SYS)], &options).unwrap();
+    assert_eq!(program.lines[..4], ["@300", "D = A", "@0", "M = D"]);
+    assert!(!program.lines.iter().any(|l| l.contains("Sys.init")));
+
+    let cpu = run(&[VmSource::new("Main", NO_SYS)], &options);
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.ram_value(0), 300);
+    assert_eq!(cpu.ram_value(300), 7);
+    assert_eq!(cpu.ram_value(16), 7);
+    assert_eq!(cpu.ram_value(1), 0); // LCL is left alone.
+}
+
+#[test]
+fn options_are_read_from_a_config_file() {
+    let text = "# project 7 style test
+bootstrap = sp-only
+
+// custom segments
+sp-base = 300
+  lcl-base=400
+global-labels = true
+keep = Main.*, Sys.init
+";
+    let mut options = TranslateOptions::default();
+    apply_config(text, &mut options).unwrap();
+    assert_eq!(options.bootstrap, Some(BootstrapMode::SpOnly));
+    assert_eq!(options.base_addresses.sp, 300);
+    assert_eq!(options.base_addresses.lcl, 400);
+    assert_eq!(options.base_addresses.arg, BaseAddresses::default().arg);
+    assert!(options.is_global_labels_option);
+    assert_eq!(options.kept_functions, ["Main.*", "Sys.init"]);
+
+    apply_config("bootstrap = auto", &mut options).unwrap();
+    assert_eq!(options.bootstrap, None);
+}
+
+#[test]
+fn bad_config_lines_are_rejected() {
+    let error = |text: &str| -> String {
+        return apply_config(text, &mut TranslateOptions::default()).unwrap_err();
+    };
+
+    assert_eq!(
+        error("bootstrap = full\n\nstack-base = 300\n"),
+        "line 3: unknown option `stack-base`."
+    );
+    assert_eq!(
+        error("sp-base 300"),
+        "line 1: expected `key = value`, found `sp-base 300`."
+    );
+    assert_eq!(
+        error("# base\nsp-base = 32768"),
+        "line 2: sp-base expects an address between 0 and 32767, found `32768`."
+    );
+    assert_eq!(
+        error("bootstrap = partial"),
+        "line 1: bootstrap expects one of full, sp-only, none or auto, found `partial`."
+    );
+    assert_eq!(
+        error("global-labels = yes"),
+        "line 1: global-labels expects true or false, found `yes`."
+    );
+}
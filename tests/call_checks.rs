@@ -0,0 +1,183 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::BootstrapMode, diagnostic::Severity, parse_program, TranslateOptions, VmSource,
+};
+
+fn options(allowed_undefined_functions: &[&str]) -> TranslateOptions {
+    return TranslateOptions {
+        bootstrap: Some(BootstrapMode::None),
+        allowed_undefined_functions: allowed_undefined_functions
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+        ..Default::default()
+    };
+}
+
+/// `(severity, file, line, message)` of every diagnostic of the program.
+fn check(
+    sources: &[VmSource],
+    options: &TranslateOptions,
+) -> Vec<(Severity, String, usize, String)> {
+    let diagnostics = match parse_program(sources, options) {
+        Ok(program) => program.warnings,
+        Err(error) => error.diagnostics,
+    };
+    return diagnostics
+        .into_iter()
+        .map(|d| (d.severity, d.file, d.span.unwrap().line, d.message))
+        .collect();
+}
+
+fn error(file: &str, line: usize, message: &str) -> (Severity, String, usize, String) {
+    return (Severity::Error, file.to_string(), line, message.to_string());
+}
+
+fn warning(file: &str, line: usize, message: &str) -> (Severity, String, usize, String) {
+    return (
+        Severity::Warning,
+        file.to_string(),
+        line,
+        message.to_string(),
+    );
+}
+
+#[test]
+fn every_call_to_an_undefined_function_is_reported() {
+    let main = "call Main.missing 0
+function Main.main 0
+call Main.missing 0
+call Main.helper 1
+return
+function Main.helper 1
+return
+";
+    assert_eq!(
+        check(&[VmSource::new("Main", main)], &options(&[])),
+        [
+            error(
+                "Main.vm",
+                1,
+                "function Main.missing is not defined in any file."
+            ),
+            error(
+                "Main.vm",
+                3,
+                "function Main.missing is not defined in any file."
+            ),
+        ]
+    );
+}
+
+#[test]
+fn the_calls_of_every_definition_of_a_duplicated_function_are_checked() {
+    let main = "function Main.main 0
+call Main.first 0
+return
+";
+    let other = "function Main.main 0
+call Main.second 0
+return
+";
+    let sources = vec![VmSource::new("Main", main), VmSource::new("Other", other)];
+    assert_eq!(
+        check(&sources, &options(&[])),
+        [
+            error(
+                "Other.vm",
+                1,
+                "function Main.main is already defined in Main.vm (line 1)."
+            ),
+            warning(
+                "Other.vm",
+                1,
+                "function Main.main should be named Other.<name> since it is defined in Other.vm."
+            ),
+            error(
+                "Main.vm",
+                2,
+                "function Main.first is not defined in any file."
+            ),
+            error(
+                "Other.vm",
+                2,
+                "function Main.second is not defined in any file."
+            ),
+        ]
+    );
+}
+
+#[test]
+fn functions_are_expected_to_be_prefixed_with_their_class() {
+    let main = "function main 0\nreturn\nfunction Main.main 0\nreturn\n";
+    assert_eq!(
+        check(&[VmSource::new("Main", main)], &options(&[])),
+        [warning(
+            "Main.vm",
+            1,
+            "function main should be named Main.<name> since it is defined in Main.vm."
+        )]
+    );
+}
+
+#[test]
+fn calls_to_the_jack_os_are_allowed_unless_the_class_is_translated() {
+    let main = "function Main.main 0
+call Math.multiply 2
+call Output.printInt 1
+call Keyboard.readInt 1
+return
+";
+    let sources = vec![VmSource::new("Main", main)];
+    assert_eq!(check(&sources, &options(&[])), []);
+
+    let math = "function Math.abs 1\npush argument 0\nreturn\n";
+    let sources = vec![VmSource::new("Main", main), VmSource::new("Math", math)];
+    assert_eq!(
+        check(&sources, &options(&[])),
+        [error(
+            "Main.vm",
+            2,
+            "function Math.multiply is not defined in any file."
+        )]
+    );
+}
+
+#[test]
+fn allowed_undefined_functions_are_not_reported() {
+    let main = "function Main.main 0
+call Lib.open 1
+call Lib.close 1
+call Net.send 2
+call Net.receive 0
+return
+";
+    let sources = vec![VmSource::new("Main", main)];
+    assert_eq!(
+        check(&sources, &options(&["Lib.*", "Net.send"])),
+        [error(
+            "Main.vm",
+            5,
+            "function Net.receive is not defined in any file."
+        )]
+    );
+}
+
+#[test]
+fn calls_with_different_argument_counts_are_a_warning() {
+    let main = "function Main.main 0
+call Main.f 1
+call Main.f 2
+return
+function Main.f 0
+return
+";
+    assert_eq!(
+        check(&[VmSource::new("Main", main)], &options(&[])),
+        [warning(
+            "Main.vm",
+            3,
+            "function Main.f is called with 2 argument(s) here but with 1 in Main.vm (line 2)."
+        )]
+    );
+}
@@ -0,0 +1,156 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::BootstrapMode,
+    parse_program,
+    parser::VmCommand,
+    translate,
+    vm_optimizer::{eliminate_dead_functions, DeadFunctionReport},
+    TranslateOptions, VmSource,
+};
+
+const SYS: &str = "function Sys.init 0
+call Main.main 0
+label END
+goto END
+";
+
+const MAIN: &str = "function Main.main 0
+call Main.helper 0
+return
+function Main.helper 0
+push constant 1
+return
+function Main.unused 0
+call Main.helper 0
+return
+";
+
+const MATH: &str = "function Math.abs 0
+push argument 0
+return
+function Math.max 0
+push argument 0
+return
+";
+
+/// Names of the functions left by `eliminate_dead_functions`, and its report.
+fn eliminate(
+    sources: &[VmSource],
+    options: &TranslateOptions,
+) -> (Vec<String>, DeadFunctionReport) {
+    let program = parse_program(sources, options).unwrap();
+    let (files, report) = eliminate_dead_functions(&program.files, options);
+    let functions = files
+        .iter()
+        .flat_map(|file| &file.commands)
+        .filter_map(|parsed| match &parsed.command {
+            VmCommand::Function { name, .. } => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    return (functions, report);
+}
+
+fn options(bootstrap: BootstrapMode, kept_functions: &[&str]) -> TranslateOptions {
+    return TranslateOptions {
+        bootstrap: Some(bootstrap),
+        is_eliminate_dead_functions_option: true,
+        kept_functions: kept_functions.iter().map(|name| name.to_string()).collect(),
+        ..Default::default()
+    };
+}
+
+#[test]
+fn functions_unreachable_from_sys_init_are_omitted() {
+    let sources = vec![
+        VmSource::new("Sys", SYS),
+        VmSource::new("Main", MAIN),
+        VmSource::new("Math", MATH),
+    ];
+    let (functions, report) = eliminate(&sources, &options(BootstrapMode::Full, &[]));
+    assert_eq!(functions, ["Sys.init", "Main.main", "Main.helper"]);
+    assert_eq!(
+        report,
+        DeadFunctionReport {
+            function_count: 6,
+            dropped: vec![
+                ("Main.unused".to_string(), 3),
+                ("Math.abs".to_string(), 3),
+                ("Math.max".to_string(), 3),
+            ],
+        }
+    );
+}
+
+#[test]
+fn top_level_calls_are_roots() {
+    let sources = vec![
+        VmSource::new("Main", &format!("call Main.helper 0\n{}", MAIN)),
+        VmSource::new("Math", MATH),
+    ];
+    let (functions, report) = eliminate(&sources, &options(BootstrapMode::SpOnly, &[]));
+    assert_eq!(functions, ["Main.helper"]);
+    assert_eq!(report.function_count, 5);
+    assert_eq!(report.dropped.len(), 4);
+}
+
+#[test]
+fn kept_functions_are_roots() {
+    let sources = vec![
+        VmSource::new("Sys", SYS),
+        VmSource::new("Main", MAIN),
+        VmSource::new("Math", MATH),
+    ];
+    let (functions, report) = eliminate(
+        &sources,
+        &options(BootstrapMode::Full, &["Math.*", "Main.unused"]),
+    );
+    assert_eq!(functions.len(), 6);
+    assert_eq!(report.dropped, []);
+
+    let (functions, _) = eliminate(&sources, &options(BootstrapMode::Full, &["Math.max"]));
+    assert_eq!(
+        functions,
+        ["Sys.init", "Main.main", "Main.helper", "Math.max"]
+    );
+}
+
+#[test]
+fn programs_without_entry_point_keep_every_function() {
+    let sources = vec![VmSource::new("Main", MAIN), VmSource::new("Math", MATH)];
+    for bootstrap in [BootstrapMode::None, BootstrapMode::SpOnly] {
+        let (functions, report) = eliminate(&sources, &options(bootstrap, &["Math.abs"]));
+        assert_eq!(
+            functions,
+            [
+                "Main.main",
+                "Main.helper",
+                "Main.unused",
+                "Math.abs",
+                "Math.max"
+            ]
+        );
+        assert_eq!(
+            report,
+            DeadFunctionReport {
+                function_count: 5,
+                dropped: vec![],
+            }
+        );
+    }
+}
+
+#[test]
+fn the_translation_reports_the_omitted_functions() {
+    let sources = vec![VmSource::new("Sys", SYS), VmSource::new("Main", MAIN)];
+    let program = translate(&sources, &options(BootstrapMode::Full, &[])).unwrap();
+    assert_eq!(
+        program.dead_function_report.unwrap().dropped,
+        [("Main.unused".to_string(), 3)]
+    );
+    assert!(!program
+        .lines
+        .iter()
+        .any(|line| line.contains("Main.unused")));
+    assert!(program.lines.contains(&"(Main.helper)".to_string()));
+}
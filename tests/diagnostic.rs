@@ -0,0 +1,42 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    diagnostic::{Diagnostic, Severity},
+    parser::Span,
+};
+
+#[test]
+fn diagnostics_are_rendered_with_a_location_and_carets() {
+    colored::control::set_override(false);
+
+    let text = "push constant 1\n\tpush foo 1   \n";
+    let span = Span {
+        line: 2,
+        column: 7,
+        len: 3,
+    };
+    let error = Diagnostic::error("Main.vm", text, span, "Memory segment foo does not exist.");
+    assert_eq!(error.snippet, "\tpush foo 1");
+    assert_eq!(
+        error.render(),
+        "error: Memory segment foo does not exist.\n \
+         --> Main.vm:2:7\n  \
+           |\n\
+         2 | \tpush foo 1\n  \
+           | \t     ^^^\n"
+    );
+
+    let span = Span {
+        line: 10,
+        column: 1,
+        len: 0,
+    };
+    let warning = Diagnostic::warning("Main.vm", "", span, "unused.");
+    assert_eq!(warning.severity, Severity::Warning);
+    assert_eq!(
+        warning.to_string(),
+        "warning: unused.\n  --> Main.vm:10:1\n   |\n10 | \n   | ^\n"
+    );
+
+    let whole_file = Diagnostic::without_span(Severity::Error, "Main.vm", "empty file.");
+    assert_eq!(whole_file.render(), "error: empty file.\n --> Main.vm\n");
+}
@@ -0,0 +1,83 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::BootstrapMode, parse_program, translate, TranslateError, TranslateOptions,
+    VmSource,
+};
+
+fn options(error_limit: Option<usize>) -> TranslateOptions {
+    return TranslateOptions {
+        error_limit,
+        bootstrap: Some(BootstrapMode::None),
+        ..Default::default()
+    };
+}
+
+/// `(file, line)` of every diagnostic.
+fn locations(error: &TranslateError) -> Vec<(String, usize)> {
+    return error
+        .diagnostics
+        .iter()
+        .map(|d| (d.file.clone(), d.span.unwrap().line))
+        .collect();
+}
+
+fn sources() -> Vec<VmSource> {
+    return vec![
+        VmSource::new(
+            "Main",
+            "push foo 1\npush constant 1\npop constant 2\nadd 3\n",
+        ),
+        VmSource::new("Good", "push constant 1\n"),
+        VmSource::new("Other", "label\n"),
+    ];
+}
+
+#[test]
+fn every_error_of_a_file_is_reported() {
+    let error = parse_program(&sources()[..1], &options(None)).unwrap_err();
+
+    assert_eq!(error.error_count(), 3);
+    assert_eq!(
+        locations(&error),
+        [
+            ("Main.vm".to_string(), 1),
+            ("Main.vm".to_string(), 3),
+            ("Main.vm".to_string(), 4)
+        ]
+    );
+    assert!(!error.is_error_limit_reached);
+    assert!(error
+        .to_string()
+        .ends_with("[ERROR] aborting due to 3 errors.\n"));
+}
+
+#[test]
+fn errors_of_every_file_are_reported_in_order() {
+    let error = translate(&sources(), &options(None)).unwrap_err();
+
+    assert_eq!(error.error_count(), 4);
+    let files: Vec<String> = locations(&error)
+        .into_iter()
+        .map(|(file, _)| file)
+        .collect();
+    assert_eq!(files, ["Main.vm", "Main.vm", "Main.vm", "Other.vm"]);
+}
+
+#[test]
+fn errors_past_the_limit_are_dropped() {
+    let error = parse_program(&sources(), &options(Some(2))).unwrap_err();
+    assert_eq!(error.error_count(), 2);
+    assert_eq!(
+        locations(&error),
+        [("Main.vm".to_string(), 1), ("Main.vm".to_string(), 3)]
+    );
+    assert!(error.is_error_limit_reached);
+    assert!(error.to_string().ends_with(
+        "[ERROR] aborting due to 2 errors (error limit reached, further errors were not reported).\n"
+    ));
+
+    // a limit that isn't exceeded is not reported.
+    let error = parse_program(&sources(), &options(Some(4))).unwrap_err();
+    assert_eq!(error.error_count(), 4);
+    assert!(!error.is_error_limit_reached);
+}
@@ -0,0 +1,164 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    hack_cpu::HackCpu, parse_program, translate, verify::Verifier, vm_optimizer::inline_functions,
+    TranslateOptions, VmSource,
+};
+
+const SYS: &str = "function Sys.init 0
+push constant 4000
+call Point.new 1
+pop static 0
+push static 0
+push constant 11
+call Point.setX 2
+pop temp 0
+push static 0
+call Point.getX 1
+push static 0
+call Point.getY 1
+call Main.max 2
+pop static 1
+push constant 3
+push constant 4
+call Main.sumSquares 2
+pop static 2
+call Point.count 0
+call Point.count 0
+add
+pop static 3
+label END
+goto END
+";
+
+const POINT: &str = "function Point.new 0
+push argument 0
+pop pointer 0
+push constant 5
+pop this 0
+push constant 7
+pop this 1
+push pointer 0
+return
+function Point.setX 0
+push argument 0
+pop pointer 0
+push argument 1
+pop this 0
+push constant 0
+return
+function Point.getX 0
+push argument 0
+pop pointer 0
+push this 0
+return
+function Point.getY 0
+push argument 0
+pop pointer 0
+push this 1
+return
+function Point.count 0
+push static 0
+push constant 1
+add
+pop static 0
+push static 0
+return
+";
+
+const MAIN: &str = "function Main.max 0
+push argument 0
+push argument 1
+gt
+if-goto FIRST
+push argument 1
+return
+label FIRST
+push argument 0
+return
+function Main.sumSquares 1
+push argument 0
+push argument 0
+add
+pop local 0
+push local 0
+push argument 1
+push argument 1
+add
+add
+return
+";
+
+/// Pointers, statics and the object created by `Point.new`.
+const RESULT_CELLS: [usize; 11] = [0, 1, 2, 3, 4, 16, 17, 18, 19, 4000, 4001];
+
+fn sources() -> Vec<VmSource> {
+    return vec![
+        VmSource::new("Sys", SYS),
+        VmSource::new("Point", POINT),
+        VmSource::new("Main", MAIN),
+    ];
+}
+
+fn run(options: &TranslateOptions) -> (Vec<i16>, Vec<String>) {
+    let program = translate(&sources(), options).unwrap();
+    let mut cpu = HackCpu::from_asm(&program.lines).unwrap();
+    cpu.run(20_000).unwrap();
+    let ram = RESULT_CELLS
+        .iter()
+        .map(|&cell| cpu.ram_value(cell))
+        .collect();
+    return (ram, program.lines);
+}
+
+#[test]
+fn inlining_leaves_the_ram_results_unchanged() {
+    for optimization_level in [0, 2] {
+        let options = TranslateOptions {
+            optimization_level,
+            ..Default::default()
+        };
+        let (expected, lines) = run(&options);
+        assert_eq!(expected[5..], [4000, 11, 14, 3, 11, 7]);
+
+        let (actual, inlined_lines) = run(&TranslateOptions {
+            inline_threshold: Some(10),
+            ..options
+        });
+        assert_eq!(actual, expected, "-O{}", optimization_level);
+
+        // getters and setters are inlined, `Main.max` (labels) and `Point.count` (statics
+        // of another file) are still called.
+        let calls_to = |lines: &[String], name: &str| {
+            lines.iter().filter(|l| **l == format!("@{}", name)).count()
+        };
+        for name in ["Point.new", "Point.setX", "Point.getX", "Main.sumSquares"] {
+            assert!(calls_to(&lines, name) > 0, "{}", name);
+            assert_eq!(calls_to(&inlined_lines, name), 0, "{}", name);
+        }
+        for name in ["Main.max", "Point.count"] {
+            assert_eq!(calls_to(&inlined_lines, name), calls_to(&lines, name));
+        }
+        assert!(inlined_lines.len() < lines.len());
+    }
+}
+
+#[test]
+fn threshold_limits_the_inlined_functions() {
+    let (_, lines) = run(&TranslateOptions {
+        inline_threshold: Some(3),
+        ..Default::default()
+    });
+    assert_eq!(lines.iter().filter(|l| *l == "@Point.getX").count(), 0);
+    assert!(lines.iter().any(|l| l == "@Point.new"));
+}
+
+#[test]
+fn inlined_program_matches_the_interpreter() {
+    let options = TranslateOptions::default();
+    let program = parse_program(&sources(), &options).unwrap();
+    let files = inline_functions(&program.files, 10);
+
+    let mut verifier = Verifier::new(&files, &options).unwrap();
+    let report = verifier.run(200).unwrap();
+    assert_eq!(report.divergence, None);
+}
@@ -0,0 +1,90 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::BootstrapMode, hack_cpu::HackCpu, translate, TranslateOptions, VmSource,
+};
+
+/// Three functions declaring their own `LOOP`.
+const SYS: &str = "function Sys.init 0
+call Main.countdown 0
+pop static 0
+call Main.skip 0
+pop static 1
+label LOOP
+goto LOOP
+";
+
+const MAIN: &str = "function Main.countdown 1
+push constant 3
+pop local 0
+label LOOP
+push local 0
+push constant 1
+sub
+pop local 0
+push local 0
+if-goto LOOP
+push constant 10
+return
+function Main.skip 0
+push constant 20
+goto LOOP
+pop temp 0
+label LOOP
+return
+";
+
+fn labels(lines: &[String]) -> Vec<&str> {
+    return lines
+        .iter()
+        .filter(|l| l.starts_with('(') && !l.contains("_ret_") && !l.contains("JUMP"))
+        .map(|l| l.as_str())
+        .collect();
+}
+
+#[test]
+fn labels_are_scoped_to_their_function() {
+    let sources = vec![VmSource::new("Sys", SYS), VmSource::new("Main", MAIN)];
+    let program = translate(&sources, &TranslateOptions::default()).unwrap();
+
+    assert_eq!(
+        labels(&program.lines),
+        [
+            "(Sys.init)",
+            "(Sys.init$LOOP)",
+            "(Main.countdown)",
+            "(Main.countdown$LOOP)",
+            "(Main.skip)",
+            "(Main.skip$LOOP)"
+        ]
+    );
+    assert!(program.lines.contains(&"@Main.skip$LOOP".to_string()));
+
+    // every jump stays in its function.
+    let mut cpu = HackCpu::from_asm(&program.lines).unwrap();
+    cpu.run(2000).unwrap();
+    assert_eq!(cpu.ram_value(16), 10);
+    assert_eq!(cpu.ram_value(17), 20);
+}
+
+#[test]
+fn labels_outside_functions_and_global_labels_are_kept_verbatim() {
+    let options = TranslateOptions {
+        bootstrap: Some(BootstrapMode::None),
+        ..Default::default()
+    };
+    let text = "label START\ngoto START\nfunction Main.f 0\nlabel LOOP\ngoto LOOP\n";
+    let sources = vec![VmSource::new("Main", text)];
+    let program = translate(&sources, &options).unwrap();
+    assert_eq!(
+        labels(&program.lines),
+        ["(START)", "(Main.f)", "(Main.f$LOOP)"]
+    );
+
+    let options = TranslateOptions {
+        is_global_labels_option: true,
+        ..options
+    };
+    let program = translate(&sources, &options).unwrap();
+    assert_eq!(labels(&program.lines), ["(START)", "(Main.f)", "(LOOP)"]);
+    assert!(program.lines.contains(&"@LOOP".to_string()));
+}
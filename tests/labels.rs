@@ -0,0 +1,157 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::BootstrapMode, diagnostic::Severity, parse_program,
+    vm_interpreter::VmInterpreter, TranslateOptions, VmSource,
+};
+
+fn options(is_global_labels_option: bool) -> TranslateOptions {
+    return TranslateOptions {
+        bootstrap: Some(BootstrapMode::None),
+        is_global_labels_option,
+        ..Default::default()
+    };
+}
+
+/// `(severity, file, line, message)` of every diagnostic of the program.
+fn check(
+    sources: &[VmSource],
+    options: &TranslateOptions,
+) -> Vec<(Severity, String, usize, String)> {
+    let diagnostics = match parse_program(sources, options) {
+        Ok(program) => program.warnings,
+        Err(error) => error.diagnostics,
+    };
+    return diagnostics
+        .into_iter()
+        .map(|d| (d.severity, d.file, d.span.unwrap().line, d.message))
+        .collect();
+}
+
+fn error(file: &str, line: usize, message: &str) -> (Severity, String, usize, String) {
+    return (Severity::Error, file.to_string(), line, message.to_string());
+}
+
+#[test]
+fn branch_targets_must_be_declared_in_their_function() {
+    let text = "function Main.a 0
+label LOOP
+goto LOOP
+goto END
+function Main.b 0
+if-goto LOOP
+label END
+return
+";
+    assert_eq!(
+        check(&[VmSource::new("Main", text)], &options(false)),
+        [
+            error(
+                "Main.vm",
+                4,
+                "label END is not declared in function Main.a."
+            ),
+            error(
+                "Main.vm",
+                6,
+                "label LOOP is not declared in function Main.b."
+            ),
+        ]
+    );
+}
+
+#[test]
+fn labels_are_declared_once_per_function() {
+    let text = "function Main.a 0
+label LOOP
+label LOOP
+function Main.b 0
+label LOOP
+return
+";
+    assert_eq!(
+        check(&[VmSource::new("Main", text)], &options(false)),
+        [error(
+            "Main.vm",
+            3,
+            "label LOOP is already declared in function Main.a (line 2)."
+        )]
+    );
+}
+
+#[test]
+fn labels_outside_functions_are_a_warning() {
+    let text = "label START\ngoto START\nfunction Main.a 0\nreturn\n";
+    assert_eq!(
+        check(&[VmSource::new("Main", text)], &options(false)),
+        [(
+            Severity::Warning,
+            "Main.vm".to_string(),
+            1,
+            "label START is declared outside any function.".to_string()
+        )]
+    );
+}
+
+#[test]
+fn global_labels_are_checked_across_the_program() {
+    let main = "function Main.a 0
+goto SHARED
+function Main.b 0
+label SHARED
+label LOOP
+return
+";
+    let sys = "function Sys.init 0
+label LOOP
+goto MISSING
+";
+    let sources = vec![VmSource::new("Main", main), VmSource::new("Sys", sys)];
+
+    // a label declared in another function can be a target.
+    assert_eq!(check(&sources[..1], &options(true)), []);
+    assert_eq!(
+        check(&sources, &options(true)),
+        [
+            error(
+                "Sys.vm",
+                2,
+                "label LOOP is already declared in the program (Main.vm, line 5)."
+            ),
+            error("Sys.vm", 3, "label MISSING is not declared in the program."),
+        ]
+    );
+
+    // scoped, the same program has other problems.
+    assert_eq!(
+        check(&sources, &options(false)),
+        [
+            error(
+                "Main.vm",
+                2,
+                "label SHARED is not declared in function Main.a."
+            ),
+            error(
+                "Sys.vm",
+                3,
+                "label MISSING is not declared in function Sys.init."
+            ),
+        ]
+    );
+}
+
+#[test]
+fn the_interpreter_resolves_global_labels_across_functions() {
+    let text = "push constant 1
+goto SKIP
+function Main.a 0
+label SKIP
+push constant 2
+";
+    let options = options(true);
+    let program = parse_program(&[VmSource::new("Main", text)], &options).unwrap();
+    let mut interpreter = VmInterpreter::new(&program.files, &options);
+    interpreter.set_ram_value(0, 256);
+    interpreter.run(10).unwrap();
+    assert_eq!(interpreter.ram_value(0), 258);
+    assert_eq!(interpreter.ram_value(257), 2);
+}
@@ -0,0 +1,174 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    parser::{parse, parse_line, ArithmeticOp, ParseError, Segment, Span, VmCommand},
+    VmSource,
+};
+
+fn command(line: &str) -> VmCommand {
+    return parse_line(line, 1).unwrap().unwrap().command;
+}
+
+fn error(line: &str) -> ParseError {
+    return parse_line(line, 1).unwrap_err();
+}
+
+#[test]
+fn every_command_form_is_parsed() {
+    assert_eq!(
+        command("push constant 7"),
+        VmCommand::Push {
+            segment: Segment::Constant,
+            index: 7
+        }
+    );
+    assert_eq!(
+        command("pop that 32767"),
+        VmCommand::Pop {
+            segment: Segment::That,
+            index: 32767
+        }
+    );
+    for segment in ["local", "argument", "this", "static", "general"] {
+        assert_eq!(
+            command(&format!("pop {} 2", segment)),
+            VmCommand::Pop {
+                segment: Segment::from_name(segment).unwrap(),
+                index: 2
+            }
+        );
+    }
+    for op in ArithmeticOp::ALL {
+        assert_eq!(command(op.name()), VmCommand::Arithmetic(op));
+    }
+    assert_eq!(
+        command("label LOOP_1"),
+        VmCommand::Label("LOOP_1".to_string())
+    );
+    assert_eq!(
+        command("goto a.b$c:d"),
+        VmCommand::Goto("a.b$c:d".to_string())
+    );
+    assert_eq!(command("if-goto END"), VmCommand::IfGoto("END".to_string()));
+    assert_eq!(
+        command("function Main.main 3"),
+        VmCommand::Function {
+            name: "Main.main".to_string(),
+            n_vars: 3
+        }
+    );
+    assert_eq!(
+        command("call Math.multiply 2"),
+        VmCommand::Call {
+            name: "Math.multiply".to_string(),
+            n_args: 2
+        }
+    );
+    assert_eq!(command("return"), VmCommand::Return);
+    assert_eq!(command("push temp 7").to_string(), "push temp 7");
+}
+
+#[test]
+fn comments_and_whitespace_are_ignored() {
+    assert_eq!(parse_line("", 1), Ok(None));
+    assert_eq!(parse_line("   // a comment", 1), Ok(None));
+
+    let parsed = parse_line("\t push  local 2 // the counter", 4)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        parsed.command,
+        VmCommand::Push {
+            segment: Segment::Local,
+            index: 2
+        }
+    );
+    // from `push` to `2`.
+    assert_eq!(
+        parsed.span,
+        Span {
+            line: 4,
+            column: 3,
+            len: 13
+        }
+    );
+}
+
+#[test]
+fn bad_segments_and_indices_are_rejected() {
+    assert_eq!(
+        error("push heap 0").message,
+        "Memory segment heap does not exist or is not supported."
+    );
+    assert_eq!(error("pop constant 1").message, "Cannot pop a constant.");
+    assert_eq!(
+        error("push constant 32768").message,
+        "32768 cannot be parsed as an unsigned integer between 0 and 32767."
+    );
+    assert_eq!(
+        error("push local -1").message,
+        "-1 cannot be parsed as an unsigned integer between 0 and 32767."
+    );
+    assert_eq!(
+        error("pop pointer 2").message,
+        "pointer 2 is out of range (0 or 1)."
+    );
+    assert!(parse_line("push pointer 1", 1).is_ok());
+    assert_eq!(
+        error("push temp 8").message,
+        "temp 8 is out of range (0 to 7)."
+    );
+    assert!(parse_line("pop temp 7", 1).is_ok());
+    assert_eq!(
+        error("call 2fast 0").message,
+        "2fast is not a valid symbol."
+    );
+    assert_eq!(
+        error("mul").message,
+        "Line starts with unknown keyword `mul`."
+    );
+}
+
+#[test]
+fn errors_point_at_the_offending_token() {
+    let span = |line: usize, column: usize, len: usize| -> Span {
+        return Span { line, column, len };
+    };
+
+    assert_eq!(
+        parse_line("push heap 0", 3).unwrap_err().span,
+        span(3, 6, 4)
+    );
+    assert_eq!(
+        parse_line("  push temp 10", 1).unwrap_err().span,
+        span(1, 13, 2)
+    );
+    assert_eq!(
+        parse_line("pop constant 1", 1).unwrap_err().span,
+        span(1, 5, 8)
+    );
+    assert_eq!(parse_line("label", 1).unwrap_err().span, span(1, 6, 1));
+
+    let missing = parse_line("push local", 1).unwrap_err();
+    assert_eq!(
+        missing.message,
+        "`push` expects a memory segment and an index."
+    );
+    assert_eq!(missing.span, span(1, 11, 1));
+
+    let extra = parse_line("add 1 // comment", 1).unwrap_err();
+    assert_eq!(extra.message, "Unexpected token `1`.");
+    assert_eq!(extra.span, span(1, 5, 1));
+}
+
+#[test]
+fn every_bad_line_of_a_file_is_reported() {
+    let source = VmSource::new("Main", "push constant 1\npush x 1\nadd\nfoo\n");
+    let errors = parse(&source).unwrap_err();
+    let lines: Vec<usize> = errors.iter().map(|e| e.span.line).collect();
+    assert_eq!(lines, [2, 4]);
+
+    let file = parse(&VmSource::new("Main", "push constant 1\n\nadd\n")).unwrap();
+    assert_eq!(file.name, "Main");
+    let lines: Vec<usize> = file.commands.iter().map(|c| c.span.line).collect();
+    assert_eq!(lines, [1, 3]);
+}
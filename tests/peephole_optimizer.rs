@@ -0,0 +1,305 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    assembler::{assemble, parse_asm, Instruction},
+    code_generator::ArithmeticStrategy,
+    hack_cpu::HackCpu,
+    optimizer::optimize,
+    translate, TranslateOptions, VmSource,
+};
+
+fn run(instructions: &[Instruction], ram: &[(usize, i16)]) -> HackCpu {
+    let mut cpu = HackCpu::new(assemble(instructions).unwrap()).unwrap();
+    for (address, value) in ram {
+        cpu.set_ram_value(*address, *value);
+    }
+    cpu.run(1000).unwrap();
+    assert!(cpu.is_halted());
+    return cpu;
+}
+
+/// Optimizes `text`, checks that both versions leave the same RAM when started from
+/// every RAM setup of `rams`, and returns the optimized lines.
+fn optimize_checked(text: &str, rams: &[&[(usize, i16)]]) -> Vec<String> {
+    let instructions = parse_asm(&text.lines().collect::<Vec<&str>>()).unwrap();
+    let optimized = optimize(&instructions);
+    for ram in rams {
+        let expected = run(&instructions, ram);
+        let actual = run(&optimized, ram);
+        assert_eq!(actual.ram, expected.ram, "{:?}", ram);
+    }
+    return optimized.iter().map(|i| i.to_string()).collect();
+}
+
+fn count(lines: &[String], line: &str) -> usize {
+    return lines.iter().filter(|l| *l == line).count();
+}
+
+#[test]
+fn push_pop_pairs_are_removed() {
+    // push 7 then pop it to RAM[20], push 8 then pop it to RAM[21].
+    let text = "@7
+D = A
+@SP
+A = M
+M = D
+@SP
+M = M + 1
+@SP
+AM = M - 1
+D = M
+@20
+M = D
+@8
+D = A
+@SP
+A = M
+M = D
+@SP
+M = M + 1
+@SP
+M = M - 1
+@SP
+A = M
+D = M
+@21
+M = D
+";
+    let optimized = optimize_checked(text, &[&[(0, 256)]]);
+    assert_eq!(count(&optimized, "M=M+1"), 0);
+    assert_eq!(count(&optimized, "M=M-1"), 0);
+    assert_eq!(count(&optimized, "D=M"), 0); // D still holds the pushed value.
+}
+
+#[test]
+fn push_pop_pairs_around_a_label_are_kept() {
+    // pushes the values stored at 256 and 257, then pops them in a loop that starts
+    // between the second push and the first pop.
+    let text = "@SP
+M = M + 1
+@SP
+M = M + 1
+(POP)
+@SP
+AM = M - 1
+D = M
+@20
+M = D + M
+@SP
+D = M
+@256
+D = D - A
+@POP
+D; JGT
+";
+    let optimized = optimize_checked(text, &[&[(0, 256), (256, 5), (257, 6)]]);
+    assert_eq!(count(&optimized, "M=M+1"), 2);
+    assert_eq!(count(&optimized, "AM=M-1"), 1);
+}
+
+#[test]
+fn redundant_loads_are_removed() {
+    let text = "@5
+D = A
+@20
+M = D
+@20
+M = M + 1
+@21
+@22
+M = D
+";
+    let optimized = optimize_checked(text, &[&[]]);
+    assert_eq!(
+        optimized,
+        ["@5", "D=A", "@20", "M=D", "M=M+1", "@22", "M=D"]
+    );
+
+    // A doesn't hold 20 anymore when jumping back to the label.
+    let text = "@3
+D = A
+@21
+M = D
+@20
+(LOOP)
+@20
+M = M + 1
+@21
+MD = M - 1
+@LOOP
+D; JGT
+";
+    let optimized = optimize_checked(text, &[&[]]);
+    assert_eq!(count(&optimized, "@20"), 2);
+}
+
+#[test]
+fn scratch_stores_overwritten_before_being_read_are_removed() {
+    let text = "@5
+D = A
+@R13
+M = D
+@6
+D = A
+@R13
+M = D
+@R13
+D = M
+@20
+M = D
+";
+    let optimized = optimize_checked(text, &[&[]]);
+    assert_eq!(
+        optimized,
+        ["@5", "D=A", "@6", "D=A", "@R13", "M=D", "D=M", "@20", "M=D"]
+    );
+}
+
+#[test]
+fn scratch_stores_read_after_a_jump_are_kept() {
+    // R13 is overwritten on the fall-through path only.
+    let text = "@5
+D = A
+@R13
+M = D
+@20
+D = M
+@READ
+D; JNE
+@6
+D = A
+@R13
+M = D
+(READ)
+@R13
+D = M
+@21
+M = D
+";
+    let optimized = optimize_checked(text, &[&[(20, 0)], &[(20, 1)]]);
+    assert_eq!(count(&optimized, "M=D"), 3);
+    let cpu = run(&parse_asm(&optimized).unwrap(), &[(20, 1)]);
+    assert_eq!(cpu.ram_value(21), 5);
+}
+
+#[test]
+fn jumps_to_the_next_instruction_are_removed() {
+    let text = "@3
+D = A
+@NEXT
+D; JGT
+(NEXT)
+@20
+M = D
+@END
+0; JMP
+(END)
+";
+    let optimized = optimize_checked(text, &[&[]]);
+    assert_eq!(optimized, ["@3", "D=A", "(NEXT)", "@20", "M=D", "(END)"]);
+}
+
+#[test]
+fn jumps_to_the_next_instruction_are_kept_when_a_is_used_after_the_label() {
+    // on both paths, M is the RAM word at the address of NEXT.
+    let text = "@20
+D = M
+@NEXT
+D; JGT
+(NEXT)
+M = D
+";
+    let optimized = optimize_checked(text, &[&[(20, 1)], &[(20, -1)]]);
+    assert_eq!(optimized, ["@20", "D=M", "@NEXT", "D;JGT", "(NEXT)", "M=D"]);
+}
+
+const SYS: &str = "function Sys.init 0
+push constant 7
+call Main.fibonacci 1
+pop static 0
+push constant 3000
+pop pointer 0
+push constant 7
+neg
+pop this 2
+push constant 5
+push constant 9
+lt
+push constant 12
+push constant 10
+and
+or
+not
+pop static 1
+label END
+goto END
+";
+
+const MAIN: &str = "function Main.fibonacci 0
+push argument 0
+push constant 2
+lt
+if-goto BASE
+push argument 0
+push constant 1
+sub
+call Main.fibonacci 1
+push argument 0
+push constant 2
+sub
+call Main.fibonacci 1
+add
+return
+label BASE
+push argument 0
+return
+";
+
+#[test]
+fn optimized_programs_compute_the_same_as_unoptimized_ones() {
+    let sources = vec![VmSource::new("Sys", SYS), VmSource::new("Main", MAIN)];
+    let variants = [
+        TranslateOptions::default(),
+        TranslateOptions {
+            arithmetic: ArithmeticStrategy::Optimized,
+            ..Default::default()
+        },
+        TranslateOptions {
+            is_shared_calls_option: true,
+            is_shared_comparisons_option: true,
+            ..Default::default()
+        },
+    ];
+    for options in variants {
+        let run = |optimization_level: u8| -> HackCpu {
+            let options = TranslateOptions {
+                optimization_level,
+                ..options.clone()
+            };
+            let program = translate(&sources, &options).unwrap();
+            let mut cpu = HackCpu::from_asm(&program.lines).unwrap();
+            cpu.run(100_000).unwrap();
+            return cpu;
+        };
+        let unoptimized = run(0);
+        let optimized = run(1);
+
+        assert!(
+            optimized.rom().len() < unoptimized.rom().len(),
+            "{:?}",
+            options
+        );
+        assert_eq!(unoptimized.ram_value(16), 13);
+        assert_eq!(unoptimized.ram_value(3002), -7);
+        assert_eq!(unoptimized.ram_value(17), !(-1 | 8));
+        // return addresses depend on the ROM layout, so the stack and the temp
+        // registers of the call/return code differ.
+        for addresses in [0..5, 16..256, 3000..3003] {
+            assert_eq!(
+                optimized.ram[addresses.clone()],
+                unoptimized.ram[addresses],
+                "{:?}",
+                options
+            );
+        }
+    }
+}
@@ -0,0 +1,83 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::{ArithmeticStrategy, BootstrapMode},
+    hack_cpu::HackCpu,
+    parse_program, translate,
+    verify::Verifier,
+    TranslateOptions, VmSource,
+};
+
+const SEGMENTS: [(&str, usize, u16); 4] = [
+    ("local", 1, 300),
+    ("argument", 2, 400),
+    ("this", 3, 3000),
+    ("that", 4, 3010),
+];
+
+/// Indices around the switch from `A = A + 1` chains to computed addresses.
+const INDICES: [u16; 11] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 100];
+
+fn program_text(segment: &str, index: u16) -> String {
+    let value = 1000 + index;
+    return format!(
+        "push constant {value}\npop {segment} {index}\npush {segment} {index}\npop temp 0\n\
+         push constant {value}\npush {segment} {index}\nadd\n"
+    );
+}
+
+#[test]
+fn push_and_pop_address_every_segment_and_index() {
+    for arithmetic in [ArithmeticStrategy::Didactic, ArithmeticStrategy::Optimized] {
+        let options = TranslateOptions {
+            bootstrap: Some(BootstrapMode::None),
+            arithmetic,
+            ..Default::default()
+        };
+        for (segment, pointer, base) in SEGMENTS {
+            for index in INDICES {
+                let sources = vec![VmSource::new("Test", &program_text(segment, index))];
+                let value = (1000 + index) as i16;
+
+                let program = translate(&sources, &options).unwrap();
+                let mut cpu = HackCpu::from_asm(&program.lines).unwrap();
+                cpu.set_ram_value(0, 256);
+                for (_, other_pointer, other_base) in SEGMENTS {
+                    cpu.set_ram_value(other_pointer, other_base as i16);
+                }
+                cpu.run(10_000).unwrap();
+
+                let case = format!("{} {} ({:?})", segment, index, arithmetic);
+                assert!(cpu.is_halted(), "{}", case);
+                assert_eq!(cpu.ram_value(pointer), base as i16, "{}", case);
+                assert_eq!(cpu.ram_value(usize::from(base + index)), value, "{}", case);
+                assert_eq!(cpu.ram_value(5), value, "{}", case);
+                assert_eq!(cpu.ram_value(0), 257, "{}", case);
+                assert_eq!(cpu.ram_value(256), 2 * value, "{}", case);
+            }
+        }
+    }
+}
+
+#[test]
+fn segment_addressing_matches_the_interpreter() {
+    let options = TranslateOptions {
+        bootstrap: Some(BootstrapMode::None),
+        ..Default::default()
+    };
+    for (segment, _, _) in SEGMENTS {
+        for index in INDICES {
+            let sources = vec![VmSource::new("Test", &program_text(segment, index))];
+            let program = parse_program(&sources, &options).unwrap();
+
+            let mut verifier = Verifier::new(&program.files, &options).unwrap();
+            verifier.set_ram_value(0, 256);
+            for (_, pointer, base) in SEGMENTS {
+                verifier.set_ram_value(pointer, base as i16);
+            }
+            let report = verifier.run(100).unwrap();
+
+            assert!(report.is_halted, "{} {}", segment, index);
+            assert_eq!(report.divergence, None, "{} {}", segment, index);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    assembler::rom_size, code_generator::BootstrapMode, hack_cpu::HackCpu, translate,
+    TranslateOptions, VmSource,
+};
+
+/// `push static i`, `push constant 5`, `op` for every operator, `count` times.
+fn comparisons(count: u16) -> String {
+    return (0..count)
+        .flat_map(|i| {
+            ["eq", "gt", "lt"].map(|op| format!("push static {}\npush constant 5\n{}\n", i, op))
+        })
+        .collect();
+}
+
+fn options(is_shared_comparisons_option: bool, optimization_level: u8) -> TranslateOptions {
+    return TranslateOptions {
+        bootstrap: Some(BootstrapMode::SpOnly),
+        is_shared_comparisons_option,
+        optimization_level,
+        ..Default::default()
+    };
+}
+
+#[test]
+fn the_report_compares_with_inlined_comparisons() {
+    for optimization_level in [0, 1, 2] {
+        let sources = vec![VmSource::new("Main", &comparisons(10))];
+        let inline = translate(&sources, &options(false, optimization_level)).unwrap();
+        assert!(inline.shared_comparisons_report.is_none());
+        let shared = translate(&sources, &options(true, optimization_level)).unwrap();
+        let report = shared.shared_comparisons_report.unwrap();
+
+        let inline_size = rom_size(&inline.lines).unwrap();
+        let shared_size = rom_size(&shared.lines).unwrap();
+        assert_eq!(
+            report.inline_instructions, inline_size,
+            "-O{}",
+            optimization_level
+        );
+        assert_eq!(
+            report.shared_instructions, shared_size,
+            "-O{}",
+            optimization_level
+        );
+        assert_eq!(
+            report.saved_instructions(),
+            inline_size as i64 - shared_size as i64
+        );
+        assert!(report.saved_instructions() > 0, "-O{}", optimization_level);
+
+        // both compute the same results.
+        let run = |lines: &[String]| -> Vec<i16> {
+            let mut cpu = HackCpu::from_asm(lines).unwrap();
+            cpu.run(10_000).unwrap();
+            assert!(cpu.is_halted());
+            return (256..286).map(|address| cpu.ram_value(address)).collect();
+        };
+        assert_eq!(run(&shared.lines), run(&inline.lines));
+    }
+}
+
+#[test]
+fn a_single_comparison_already_saves_rom_words() {
+    let sources = vec![VmSource::new(
+        "Main",
+        "push static 1\npush constant 5\neq\n",
+    )];
+    let program = translate(&sources, &options(true, 0)).unwrap();
+    let report = program.shared_comparisons_report.unwrap();
+    assert_eq!(
+        (report.inline_instructions, report.shared_instructions),
+        (51, 40)
+    );
+    assert_eq!(report.saved_instructions(), 11);
+
+    // without comparisons, nothing is shared.
+    let sources = vec![VmSource::new(
+        "Main",
+        "push static 1\npush constant 5\nadd\n",
+    )];
+    let program = translate(&sources, &options(true, 0)).unwrap();
+    assert_eq!(
+        program
+            .shared_comparisons_report
+            .unwrap()
+            .saved_instructions(),
+        0
+    );
+}
@@ -0,0 +1,97 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    assembler::rom_size, code_generator::BootstrapMode, translate, TranslateOptions, VmSource,
+};
+
+fn options() -> TranslateOptions {
+    return TranslateOptions {
+        bootstrap: Some(BootstrapMode::None),
+        ..Default::default()
+    };
+}
+
+/// `function name 0` followed by `n` copies of `command`, then `return`.
+fn function_text(name: &str, command: &str, n: usize) -> String {
+    return format!("function {} 0\n{}return\n", name, command.repeat(n));
+}
+
+#[test]
+fn a_program_over_the_rom_size_is_an_error_listing_the_biggest_functions() {
+    let text: String = (1..=7)
+        .map(|k| {
+            function_text(
+                &format!("Main.f{}", k),
+                "push local 0\npop this 3\n",
+                k * 400,
+            )
+        })
+        .collect();
+    let error = translate(&[VmSource::new("Main", &text)], &options()).unwrap_err();
+
+    assert_eq!(error.error_count(), 1);
+    let message = &error.diagnostics[0].message;
+    assert!(message.starts_with("the program has "), "{}", message);
+    assert!(message.contains("the HACK ROM holds 32768."), "{}", message);
+    let biggest = message.split("Biggest functions: ").nth(1).unwrap();
+    let names: Vec<&str> = biggest
+        .split(", ")
+        .map(|contributor| contributor.split(' ').next().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["Main.f7", "Main.f6", "Main.f5", "Main.f4", "Main.f3"]
+    );
+}
+
+#[test]
+fn a_program_under_the_rom_size_is_translated() {
+    let text = function_text("Main.f", "push local 0\npop this 3\n", 400);
+    let sources = vec![VmSource::new("Main", &text)];
+    let program = translate(&sources, &options()).unwrap();
+    assert!(program.size_report.is_none());
+
+    let options = TranslateOptions {
+        is_size_report_option: true,
+        ..options()
+    };
+    let report = translate(&sources, &options).unwrap().size_report.unwrap();
+    assert_eq!(report.total_instructions, rom_size(&program.lines).unwrap());
+    assert!(report.total_instructions < 32768);
+    assert!(report.errors().is_empty());
+}
+
+#[test]
+fn more_than_240_statics_is_an_error_listing_the_files_with_the_most_statics() {
+    let statics = |count: u16| -> String {
+        return (0..count)
+            .map(|i| format!("push constant {}\npop static {}\n", i, i))
+            .collect();
+    };
+    let sources = vec![
+        VmSource::new("Small", &statics(10)),
+        VmSource::new("Big", &statics(150)),
+        VmSource::new("Medium", &statics(81)),
+    ];
+    let error = translate(&sources, &options()).unwrap_err();
+
+    assert_eq!(error.error_count(), 1);
+    let diagnostic = &error.diagnostics[0];
+    assert_eq!(diagnostic.file, "Big.vm");
+    assert_eq!(
+        diagnostic.message,
+        "the program has 241 static variables, RAM 16..255 holds 240. \
+         Files with the most statics: Big.vm (150), Medium.vm (81), Small.vm (10)."
+    );
+
+    // 240 statics fit.
+    let sources = vec![
+        VmSource::new("Big", &statics(150)),
+        VmSource::new("Medium", &statics(90)),
+    ];
+    let options = TranslateOptions {
+        is_size_report_option: true,
+        ..options()
+    };
+    let report = translate(&sources, &options).unwrap().size_report.unwrap();
+    assert_eq!(report.static_count(), 240);
+}
@@ -0,0 +1,189 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    assembler::rom_size,
+    stats::{CostProfile, StatsFormat},
+    translate, TranslateOptions, VmSource,
+};
+
+const SYS: &str = "function Sys.init 0
+call Main.main 0
+pop temp 0
+label END
+goto END
+";
+
+const MAIN: &str = "function Main.main 0
+push constant 1
+push constant 2
+call Main.add 2
+return
+function Main.add 0
+push argument 0
+push argument 1
+add
+return
+";
+
+fn sources() -> Vec<VmSource> {
+    return vec![VmSource::new("Sys", SYS), VmSource::new("Main", MAIN)];
+}
+
+fn profile(options: TranslateOptions) -> (CostProfile, Vec<String>) {
+    let options = TranslateOptions {
+        is_stats_option: true,
+        ..options
+    };
+    let program = translate(&sources(), &options).unwrap();
+    return (program.cost_profile.unwrap(), program.lines);
+}
+
+#[test]
+fn stats_are_only_computed_when_requested() {
+    let program = translate(&sources(), &TranslateOptions::default()).unwrap();
+    assert!(program.cost_profile.is_none());
+}
+
+#[test]
+fn every_instruction_is_attributed_to_a_function_and_a_command_kind() {
+    let (profile, lines) = profile(TranslateOptions::default());
+
+    let names: Vec<String> = profile.functions.iter().map(|f| f.size.name()).collect();
+    assert_eq!(names, ["Main.main", "Main.add", "Sys.init"]); // the most expensive first.
+    for cost in &profile.functions {
+        assert_eq!(cost.vm_commands, 5);
+        let by_kind: usize = cost.instructions_by_kind.iter().sum();
+        assert_eq!(by_kind, cost.size.instructions, "{}", cost.size.name());
+    }
+    assert_eq!(profile.total_instructions(), rom_size(&lines).unwrap());
+
+    // push, pop, arith, call, return, branch.
+    let sys_init = &profile.functions[2].instructions_by_kind;
+    assert_eq!(sys_init[0], 0);
+    assert!(sys_init[1] > 0 && sys_init[3] > 0 && sys_init[5] > 0);
+    assert_eq!((sys_init[2], sys_init[4]), (0, 0));
+    let main_add = &profile.functions[1].instructions_by_kind;
+    assert!(main_add[0] > 0 && main_add[2] > 0 && main_add[4] > 0);
+    assert_eq!((main_add[1], main_add[3], main_add[5]), (0, 0, 0));
+}
+
+#[test]
+fn stats_are_counted_before_the_peephole_optimizer() {
+    let (unoptimized, _) = profile(TranslateOptions::default());
+    let (optimized, lines) = profile(TranslateOptions {
+        optimization_level: 1,
+        ..Default::default()
+    });
+    assert_eq!(optimized, unoptimized);
+    assert!(rom_size(&lines).unwrap() < optimized.total_instructions());
+}
+
+#[test]
+fn stats_are_rendered_as_a_table() {
+    let (profile, _) = profile(TranslateOptions::default());
+    let table = profile.render(StatsFormat::Table);
+    let lines: Vec<&str> = table.lines().collect();
+
+    assert_eq!(lines.len(), 6);
+    let header: Vec<&str> = lines[0].split_whitespace().collect();
+    assert_eq!(
+        header,
+        ["function", "file", "vm", "asm", "push", "pop", "arith", "call", "return", "branch"]
+    );
+    for (line, cost) in lines[1..4].iter().zip(&profile.functions) {
+        let mut expected = vec![
+            cost.size.name(),
+            cost.size.file.clone(),
+            cost.vm_commands.to_string(),
+            cost.size.instructions.to_string(),
+        ];
+        expected.extend(cost.instructions_by_kind.iter().map(|n| n.to_string()));
+        assert_eq!(line.split_whitespace().collect::<Vec<&str>>(), expected);
+    }
+    assert_eq!(
+        lines[4],
+        format!(
+            "bootstrap and shared routines: {} instructions",
+            profile.startup_instructions
+        )
+    );
+    assert_eq!(
+        lines[5],
+        format!("total: {} instructions", profile.total_instructions())
+    );
+}
+
+#[test]
+fn stats_are_rendered_as_json() {
+    let (profile, _) = profile(TranslateOptions::default());
+    let json = profile.render(StatsFormat::Json);
+    let add = &profile.functions[1];
+    let by_kind = add.instructions_by_kind;
+
+    assert!(json.starts_with(&format!(
+        "{{\n  \"startup_instructions\": {},\n  \"total_instructions\": {},\n  \"functions\": [\n",
+        profile.startup_instructions,
+        profile.total_instructions()
+    )));
+    assert!(json.contains(&format!(
+        "{{\"function\": \"Main.add\", \"file\": \"Main.vm\", \"vm_commands\": 5, \
+         \"instructions\": {}, \"by_kind\": {{\"push\": {}, \"pop\": {}, \"arith\": {}, \
+         \"call\": {}, \"return\": {}, \"branch\": {}}}}}",
+        add.size.instructions,
+        by_kind[0],
+        by_kind[1],
+        by_kind[2],
+        by_kind[3],
+        by_kind[4],
+        by_kind[5]
+    )));
+    assert_eq!(json.matches("\"function\": ").count(), 3);
+    assert!(json.ends_with("}\n  ]\n}\n"));
+}
+
+#[test]
+fn stats_are_rendered_as_csv() {
+    let (profile, _) = profile(TranslateOptions::default());
+    let csv = profile.render(StatsFormat::Csv);
+    let lines: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(
+        lines[0],
+        "function,file,vm_commands,instructions,push,pop,arith,call,return,branch"
+    );
+    assert_eq!(lines.len(), 4);
+    let sys_init = &profile.functions[2];
+    let by_kind: Vec<String> = sys_init
+        .instructions_by_kind
+        .iter()
+        .map(|n| n.to_string())
+        .collect();
+    assert_eq!(
+        lines[3],
+        format!(
+            "Sys.init,Sys.vm,5,{},{}",
+            sys_init.size.instructions,
+            by_kind.join(",")
+        )
+    );
+}
+
+#[test]
+fn commands_outside_functions_get_their_own_row() {
+    let sources = vec![VmSource::new("Test", "push constant 1\npop temp 0\n")];
+    let options = TranslateOptions {
+        is_stats_option: true,
+        ..Default::default()
+    };
+    let profile = translate(&sources, &options).unwrap().cost_profile.unwrap();
+
+    assert_eq!(profile.functions.len(), 1);
+    assert_eq!(
+        profile.functions[0].size.name(),
+        "Test.vm (outside functions)"
+    );
+    let csv = profile.render(StatsFormat::Csv);
+    assert!(csv.lines().nth(1).unwrap().starts_with(",Test.vm,2,"));
+    assert!(profile
+        .render(StatsFormat::Json)
+        .contains("{\"function\": null, \"file\": \"Test.vm\", \"vm_commands\": 2,"));
+}
@@ -0,0 +1,205 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::BootstrapMode, hack_cpu::HackCpu, translate, TranslateOptions, VmSource,
+};
+
+/// First address above the stack region (the heap starts at 2048).
+const STACK_END: i16 = 2048;
+
+const SYS: &str = "function Sys.init 0
+push constant 3000
+pop pointer 0
+push argument 0
+push constant 0
+call Main.sum 2
+pop static 0
+push argument 1
+call Main.isEven 1
+pop static 1
+push pointer 0
+pop static 2
+label END
+goto END
+";
+
+/// `sum(n, acc)` recurses n times, `isEven(n)` and `isOdd(n, a, b)` call each other with a
+/// different number of arguments.
+const MAIN: &str = "function Main.sum 0
+push argument 0
+push constant 0
+eq
+if-goto BASE
+push argument 0
+push constant 1
+sub
+push argument 1
+push argument 0
+add
+call Main.sum 2
+return
+label BASE
+push argument 1
+return
+function Main.isEven 0
+push argument 0
+push constant 0
+eq
+if-goto YES
+push argument 0
+push constant 1
+sub
+push constant 7
+push constant 9
+call Main.isOdd 3
+return
+label YES
+push constant 0
+not
+return
+function Main.isOdd 2
+push constant 5000
+pop pointer 0
+push argument 1
+push argument 2
+add
+pop local 1
+push argument 0
+push constant 0
+eq
+if-goto NO
+push argument 0
+push constant 1
+sub
+call Main.isEven 1
+return
+label NO
+push local 1
+return
+";
+
+struct Run {
+    sum: i16,
+    is_even: i16,
+    this: i16,
+    max_sp: i16,
+}
+
+/// Runs the program with `n` as argument of `sum` and `is_even` as argument of `isEven`,
+/// until it stores THIS in its last static.
+fn run(n: i16, is_even: i16, options: &TranslateOptions) -> Run {
+    // Sys.init reads its arguments from the bootstrap call frame: ARG = 256.
+    let sys = SYS
+        .replacen("push argument 0", &format!("push constant {}", n), 1)
+        .replacen("push argument 1", &format!("push constant {}", is_even), 1);
+    let sources = vec![VmSource::new("Sys", &sys), VmSource::new("Main", MAIN)];
+    let program = translate(&sources, options).unwrap();
+    let mut cpu = HackCpu::from_asm(&program.lines).unwrap();
+
+    let mut max_sp = 0;
+    for _ in 0..8_000_000 {
+        if cpu.step().is_err() || cpu.ram_value(18) != 0 {
+            break;
+        }
+        max_sp = max_sp.max(cpu.ram_value(0));
+    }
+    return Run {
+        sum: cpu.ram_value(16),
+        is_even: cpu.ram_value(17),
+        this: cpu.ram_value(18),
+        max_sp,
+    };
+}
+
+#[test]
+fn deep_tail_recursion_stays_in_the_stack_region() {
+    for is_shared_calls_option in [false, true] {
+        let options = TranslateOptions {
+            is_tail_calls_option: true,
+            is_shared_calls_option,
+            ..Default::default()
+        };
+        let result = run(10000, 1001, &options);
+        assert_eq!(result.sum, 1032); // 50005000 mod 2^16
+        assert_eq!(result.is_even, 16);
+        assert_eq!(result.this, 3000);
+        assert!(result.max_sp < 300, "SP reached {}", result.max_sp);
+
+        let result = run(1000, 1000, &options);
+        assert_eq!(result.sum, -23788); // 500500 mod 2^16
+        assert_eq!(result.is_even, -1);
+        assert!(result.max_sp < 300, "SP reached {}", result.max_sp);
+    }
+}
+
+#[test]
+fn deep_recursion_overflows_the_stack_region_without_tail_calls() {
+    let result = run(1000, 0, &TranslateOptions::default());
+    assert!(result.max_sp > STACK_END, "SP reached {}", result.max_sp);
+}
+
+#[test]
+fn tail_calls_give_the_same_results() {
+    for (n, is_even) in [(0, 0), (1, 1), (10, 7), (25, 12)] {
+        let expected = run(n, is_even, &TranslateOptions::default());
+        let actual = run(
+            n,
+            is_even,
+            &TranslateOptions {
+                is_tail_calls_option: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            (actual.sum, actual.is_even, actual.this),
+            (expected.sum, expected.is_even, expected.this),
+            "n = {}, is_even = {}",
+            n,
+            is_even
+        );
+        assert!(actual.max_sp <= expected.max_sp);
+    }
+}
+
+#[test]
+fn top_level_calls_are_never_tail_calls() {
+    let main = "push constant 4
+call Main.double 1
+return
+function Main.double 0
+push argument 0
+push argument 0
+add
+return
+function Main.quadruple 0
+push argument 0
+call Main.double 1
+return
+";
+    let translate_main = |main: &str, is_tail_calls_option: bool| -> Vec<String> {
+        let options = TranslateOptions {
+            bootstrap: Some(BootstrapMode::SpOnly),
+            is_tail_calls_option,
+            ..Default::default()
+        };
+        return translate(&[VmSource::new("Main", main)], &options)
+            .unwrap()
+            .lines;
+    };
+    // only the call in Main.quadruple is a tail call.
+    let top_level = main.split("function Main.quadruple").next().unwrap();
+    assert_eq!(
+        translate_main(top_level, true),
+        translate_main(top_level, false)
+    );
+    assert_ne!(translate_main(main, true), translate_main(main, false));
+
+    // the top-level call returns to the top-level code with the doubled value.
+    let lines = translate_main(main, true);
+    let mut cpu = HackCpu::from_asm(&lines).unwrap();
+    for _ in 0..1000 {
+        if cpu.step().is_err() || (cpu.ram_value(0), cpu.ram_value(256)) == (257, 8) {
+            break;
+        }
+    }
+    assert_eq!((cpu.ram_value(0), cpu.ram_value(256)), (257, 8));
+}
@@ -0,0 +1,149 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    hack_cpu::HackCpu,
+    test_script::{
+        compare_output, parse_script, run_script, CompareFailure, OutputColumn, ScriptCommand,
+        ScriptVariable,
+    },
+    translate, TranslateOptions, VmSource,
+};
+
+const SIMPLE_ADD_VM: &str = "// Pushes and adds two constants.
+push constant 7
+push constant 8
+add
+";
+
+const SIMPLE_ADD_TST: &str = "// Tests SimpleAdd.asm on the CPU emulator.
+load SimpleAdd.asm,
+output-file SimpleAdd.out,
+compare-to SimpleAdd.cmp,
+output-list RAM[0]%D2.6.2 RAM[256]%D2.6.2;
+
+set RAM[0] 256,  // initializes the stack pointer
+
+repeat 60 {      // enough cycles to complete the execution
+  ticktock;
+}
+
+output;          // the stack pointer and the stack base
+";
+
+const SIMPLE_ADD_CMP: &str = "|  RAM[0]  | RAM[256] |
+|     257  |      15  |
+";
+
+/// Output of `script` run against the translation of `vm_text`.
+fn run_output(script: &str, vm_text: &str) -> Vec<String> {
+    let commands = parse_script(script).unwrap();
+    let mut loader = |_: Option<&str>| -> Result<HackCpu, String> {
+        let sources = vec![VmSource::new("SimpleAdd", vm_text)];
+        let program = translate(&sources, &TranslateOptions::default()).unwrap();
+        return HackCpu::from_asm(&program.lines).map_err(|e| e.to_string());
+    };
+    let run = run_script(&commands, &mut loader).unwrap();
+    assert_eq!(run.output_file.as_deref(), Some("SimpleAdd.out"));
+    assert_eq!(run.compare_file.as_deref(), Some("SimpleAdd.cmp"));
+    return run.output;
+}
+
+#[test]
+fn simple_add_passes_its_compare_file() {
+    let output = run_output(SIMPLE_ADD_TST, SIMPLE_ADD_VM);
+    assert_eq!(
+        output,
+        ["|  RAM[0]  | RAM[256] |", "|     257  |      15  |"]
+    );
+    assert_eq!(compare_output(&output, SIMPLE_ADD_CMP), None);
+}
+
+#[test]
+fn a_wrong_result_fails_the_comparison() {
+    let output = run_output(SIMPLE_ADD_TST, &SIMPLE_ADD_VM.replace("add", "sub"));
+    assert_eq!(
+        compare_output(&output, SIMPLE_ADD_CMP),
+        Some(CompareFailure {
+            line: 2,
+            expected: "|     257  |      15  |".to_string(),
+            actual: "|     257  |      -1  |".to_string(),
+        })
+    );
+}
+
+#[test]
+fn missing_and_extra_lines_fail_the_comparison() {
+    let header = "|  RAM[0]  | RAM[256] |".to_string();
+    let values = "|     257  |      15  |".to_string();
+
+    let failure = compare_output(&[], SIMPLE_ADD_CMP).unwrap();
+    assert_eq!((failure.line, failure.actual.as_str()), (1, ""));
+
+    let failure = compare_output(std::slice::from_ref(&header), SIMPLE_ADD_CMP).unwrap();
+    assert_eq!(failure.line, 2);
+    assert_eq!(failure.expected, values);
+    assert_eq!(failure.actual, "");
+
+    let failure = compare_output(&[header, values.clone(), values], SIMPLE_ADD_CMP).unwrap();
+    assert_eq!((failure.line, failure.expected.as_str()), (3, ""));
+}
+
+#[test]
+fn comparison_ignores_whitespace_and_blank_lines() {
+    let output = vec!["|RAM[0]|RAM[256]|".to_string(), "|257|15|".to_string()];
+    let compare_text = format!("\n{}\n\n", SIMPLE_ADD_CMP);
+    assert_eq!(compare_output(&output, &compare_text), None);
+}
+
+#[test]
+fn scripts_are_parsed_without_comments() {
+    let script = "/* header\n   comment */ set RAM[0] 256, // stack\n\
+                  repeat 2 { ticktock; } echo \"a b\";\noutput-list A%X1.4.1 D;";
+    let commands = parse_script(script).unwrap();
+    assert_eq!(
+        commands,
+        [
+            ScriptCommand::Set(ScriptVariable::Ram(0), 256),
+            ScriptCommand::Repeat(2, vec![ScriptCommand::TickTock]),
+            ScriptCommand::Echo("a b".to_string()),
+            ScriptCommand::OutputList(vec![
+                OutputColumn {
+                    name: "A".to_string(),
+                    variable: ScriptVariable::A,
+                    format: 'X',
+                    pad_left: 1,
+                    len: 4,
+                    pad_right: 1,
+                },
+                OutputColumn {
+                    name: "D".to_string(),
+                    variable: ScriptVariable::D,
+                    format: 'B',
+                    pad_left: 1,
+                    len: 16,
+                    pad_right: 1,
+                },
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn script_errors_report_their_line() {
+    let error = parse_script("load,\n\nrepeat 3 {\n  ticktock;\n").unwrap_err();
+    assert_eq!((error.line, error.message.as_str()), (4, "missing `}`."));
+
+    let error = parse_script("load,\nset RAM[32768] 1;").unwrap_err();
+    assert_eq!(
+        (error.line, error.message.as_str()),
+        (2, "unknown variable `RAM[32768]`.")
+    );
+
+    let error = parse_script("output-list RAM[0]%D2.6;").unwrap_err();
+    assert_eq!(error.message, "cannot parse the output format `D2.6`.");
+
+    let error = parse_script("\nvm-step;").unwrap_err();
+    assert_eq!(
+        (error.line, error.message.as_str()),
+        (2, "unsupported command `vm-step`.")
+    );
+}
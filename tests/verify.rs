@@ -0,0 +1,85 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::{ArithmeticStrategy, BootstrapMode},
+    parse_program,
+    verify::{Verifier, VerifyReport},
+    TranslateOptions, VmSource,
+};
+
+/// Sets `this` to 3030 and `that` to 3040 through `pointer`, then writes through them.
+/// D doesn't hold the popped value when `pop pointer` starts: `if-goto` leaves the
+/// condition in D and the optimized `add` leaves its second operand.
+const POINTER_TEST: &str = "push constant 3030
+push constant 0
+if-goto SKIP
+label SKIP
+pop pointer 0
+push constant 3000
+push constant 40
+add
+pop pointer 1
+push constant 32
+pop this 2
+push constant 46
+pop that 6
+push pointer 0
+push pointer 1
+add
+";
+
+fn verify(text: &str, arithmetic: ArithmeticStrategy) -> (Verifier, VerifyReport) {
+    let options = TranslateOptions {
+        bootstrap: Some(BootstrapMode::None),
+        arithmetic,
+        ..Default::default()
+    };
+    let program = parse_program(&[VmSource::new("Test", text)], &options).unwrap();
+    let mut verifier = Verifier::new(&program.files, &options).unwrap();
+    verifier.set_ram_value(0, 256);
+    let report = verifier.run(100).unwrap();
+    return (verifier, report);
+}
+
+#[test]
+fn pop_pointer_sets_this_and_that() {
+    for arithmetic in [ArithmeticStrategy::Didactic, ArithmeticStrategy::Optimized] {
+        let (verifier, report) = verify(POINTER_TEST, arithmetic);
+
+        assert_eq!(report.divergence, None, "{:?}", arithmetic);
+        assert!(report.is_halted);
+        assert_eq!(report.steps, 16);
+        assert_eq!(verifier.cpu.ram_value(3), 3030, "{:?}", arithmetic);
+        assert_eq!(verifier.cpu.ram_value(4), 3040, "{:?}", arithmetic);
+        assert_eq!(verifier.cpu.ram_value(3032), 32);
+        assert_eq!(verifier.cpu.ram_value(3046), 46);
+        assert_eq!(verifier.cpu.ram_value(256), 6070);
+    }
+}
+
+#[test]
+fn every_command_is_compared_with_the_interpreter() {
+    let text = "function Sys.init 0
+push constant 10
+push constant 3
+call Math.sub 2
+pop static 0
+label END
+goto END
+function Math.sub 1
+push argument 0
+push argument 1
+sub
+pop local 0
+push local 0
+return
+";
+    let options = TranslateOptions::default();
+    let program = parse_program(&[VmSource::new("Sys", text)], &options).unwrap();
+    let mut verifier = Verifier::new(&program.files, &options).unwrap();
+    let report = verifier.run(50).unwrap();
+
+    assert_eq!(report.divergence, None);
+    assert_eq!(report.steps, 50); // Sys.init loops forever.
+    assert_eq!(verifier.interpreter.ram_value(16), 7);
+    assert_eq!(verifier.cpu.ram_value(16), 7);
+}
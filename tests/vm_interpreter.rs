@@ -0,0 +1,43 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::BootstrapMode,
+    parse_program,
+    vm_interpreter::{VmInterpreter, VmRuntimeError},
+    TranslateOptions, VmSource,
+};
+
+/// Runs `text` with the given RAM values set, returning the runtime error.
+fn runtime_error(text: &str, ram: &[(usize, i16)]) -> VmRuntimeError {
+    let options = TranslateOptions {
+        bootstrap: Some(BootstrapMode::None),
+        ..Default::default()
+    };
+    let program = parse_program(&[VmSource::new("Test", text)], &options).unwrap();
+    let mut interpreter = VmInterpreter::new(&program.files, &options);
+    for (address, value) in ram {
+        interpreter.set_ram_value(*address, *value);
+    }
+    return interpreter.run(100).unwrap_err();
+}
+
+#[test]
+fn pop_outside_the_ram_is_a_runtime_error() {
+    let error = runtime_error("pop temp 0\n", &[(0, -1)]);
+    assert_eq!(error.file, "Test.vm");
+    assert_eq!(error.line, 1);
+    assert_eq!(
+        error.message,
+        "RAM[65534] is out of bounds (RAM holds 32768 words)."
+    );
+}
+
+#[test]
+fn return_with_a_frame_outside_the_ram_is_a_runtime_error() {
+    let text = "function Test.f 0\npush constant 1\nreturn\n";
+    let error = runtime_error(text, &[(0, 256), (1, -1)]);
+    assert_eq!(error.line, 3);
+    assert_eq!(
+        error.message,
+        "RAM[65534] is out of bounds (RAM holds 32768 words)."
+    );
+}
@@ -0,0 +1,173 @@
+#![allow(clippy::needless_return)]
+use vm_translator::{
+    code_generator::{ArithmeticStrategy, BootstrapMode},
+    hack_cpu::HackCpu,
+    parse_program,
+    parser::parse,
+    translate,
+    verify::Verifier,
+    vm_optimizer::optimize_commands,
+    TranslateOptions, VmSource,
+};
+
+/// Commands of `text` after `optimize_commands`, one per line.
+fn optimized(text: &str) -> Vec<String> {
+    let file = parse(&VmSource::new("Test", text)).unwrap();
+    return optimize_commands(&file.commands)
+        .iter()
+        .map(|parsed| parsed.command.to_string())
+        .collect();
+}
+
+/// RAM[5] (temp 0) after running the translation of `text` with `options`.
+fn temp_0_after_run(text: &str, options: &TranslateOptions) -> i16 {
+    let options = TranslateOptions {
+        bootstrap: Some(BootstrapMode::None),
+        ..options.clone()
+    };
+    let program = translate(&[VmSource::new("Test", text)], &options).unwrap();
+    let mut cpu = HackCpu::from_asm(&program.lines).unwrap();
+    cpu.set_ram_value(0, 256);
+    cpu.run(10_000).unwrap();
+    assert!(cpu.is_halted());
+    return cpu.ram_value(5);
+}
+
+#[test]
+fn constants_are_folded() {
+    assert_eq!(
+        optimized("push constant 3\npush constant 4\nadd\n"),
+        ["push constant 7"]
+    );
+    assert_eq!(
+        optimized("push constant 12\npush constant 10\nand\npush constant 1\nor\n"),
+        ["push constant 9"]
+    );
+    assert_eq!(
+        optimized("push constant 7\npush constant 7\neq\nnot\n"),
+        ["push constant 0"]
+    );
+}
+
+#[test]
+fn folding_wraps_around_and_encodes_large_results_with_not() {
+    // 32767 + 1 = -32768, which `push constant` can't express: pushed as !32767.
+    assert_eq!(
+        optimized("push constant 32767\npush constant 1\nadd\n"),
+        ["push constant 32767", "not"]
+    );
+    // -5 - 2 = -7 = !6.
+    assert_eq!(
+        optimized("push constant 5\nneg\npush constant 2\nsub\n"),
+        ["push constant 6", "not"]
+    );
+    // true is !0.
+    assert_eq!(
+        optimized("push constant 1\npush constant 2\nlt\n"),
+        ["push constant 0", "not"]
+    );
+    // already as short as it gets.
+    assert_eq!(
+        optimized("push constant 1\nneg\n"),
+        ["push constant 1", "neg"]
+    );
+}
+
+#[test]
+fn comparisons_fold_like_the_generated_code() {
+    let code_options = [
+        TranslateOptions::default(),
+        TranslateOptions {
+            arithmetic: ArithmeticStrategy::Optimized,
+            ..Default::default()
+        },
+        TranslateOptions {
+            is_shared_comparisons_option: true,
+            ..Default::default()
+        },
+    ];
+    // x - y overflows for the first two pairs: 32767 - (-1) and -32768 - 1.
+    let operands = [
+        ("push constant 32767\npush constant 1\nneg\n", [0, -1, 0]),
+        ("push constant 32767\nnot\npush constant 1\n", [0, 0, -1]),
+        ("push constant 5\nneg\npush constant 3\nneg\n", [0, 0, -1]),
+        ("push constant 0\npush constant 0\n", [-1, 0, 0]),
+    ];
+    for (operands, expected) in operands {
+        for (comparison, expected) in ["eq", "gt", "lt"].iter().zip(expected) {
+            let text = format!("{}{}\npop temp 0\n", operands, comparison);
+            for options in &code_options {
+                let folded = TranslateOptions {
+                    optimization_level: 2,
+                    ..options.clone()
+                };
+                assert_eq!(temp_0_after_run(&text, options), expected, "{}", text);
+                assert_eq!(temp_0_after_run(&text, &folded), expected, "{}", text);
+            }
+        }
+    }
+}
+
+#[test]
+fn overflowing_comparisons_are_folded_like_the_interpreter() {
+    let text = "push constant 32767\npush constant 1\nneg\ngt\npop temp 0\n";
+    assert_eq!(optimized(text), ["push constant 0", "not", "pop temp 0"]);
+
+    // the unfolded translation agrees with the interpreter after every command.
+    let options = TranslateOptions {
+        bootstrap: Some(BootstrapMode::None),
+        ..Default::default()
+    };
+    for arithmetic in [ArithmeticStrategy::Didactic, ArithmeticStrategy::Optimized] {
+        let options = TranslateOptions {
+            arithmetic,
+            ..options.clone()
+        };
+        let program = parse_program(&[VmSource::new("Test", text)], &options).unwrap();
+        let mut verifier = Verifier::new(&program.files, &options).unwrap();
+        verifier.set_ram_value(0, 256);
+        let report = verifier.run(100).unwrap();
+        assert_eq!(report.divergence, None, "{:?}", arithmetic);
+        assert_eq!(verifier.interpreter.ram_value(5), -1);
+    }
+}
+
+#[test]
+fn constants_are_not_folded_across_a_label() {
+    let text = "push constant 1\nlabel L\npush constant 2\nadd\n";
+    assert_eq!(
+        optimized(text),
+        ["push constant 1", "label L", "push constant 2", "add"]
+    );
+}
+
+#[test]
+fn push_pop_pairs_are_fused() {
+    assert_eq!(
+        optimized("push local 0\npop static 3\n"),
+        ["move local 0 to static 3"]
+    );
+    assert_eq!(
+        optimized("push constant 5\npop that 2\n"),
+        ["move constant 5 to that 2"]
+    );
+    assert_eq!(optimized("push argument 1\npop argument 1\n"), [""; 0]);
+}
+
+#[test]
+fn temp_round_trips_are_removed_when_the_temp_is_overwritten() {
+    assert_eq!(
+        optimized("pop temp 0\npush temp 0\nadd\npop temp 0\n"),
+        ["add", "pop temp 0"]
+    );
+    // temp 0 is read again.
+    assert_eq!(
+        optimized("pop temp 0\npush temp 0\nadd\npush temp 0\n"),
+        ["pop temp 0", "push temp 0", "add", "push temp 0"]
+    );
+    // the callee may read temp 0.
+    assert_eq!(
+        optimized("pop temp 0\npush temp 0\ncall Foo.bar 1\npop temp 0\n"),
+        ["pop temp 0", "push temp 0", "call Foo.bar 1", "pop temp 0"]
+    );
+}